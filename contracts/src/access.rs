@@ -0,0 +1,224 @@
+//!
+//! Ownership and role-based access control.
+//!
+//! `Counter` has a single owner, handed over in two steps (`transferOwnership` then
+//! `acceptOwnership`), and OpenZeppelin-style roles. The owner holds `DEFAULT_ADMIN_ROLE`, which
+//! administers every other role, and accounts holding `MINTER_ROLE` may mint swords directly.
+//!
+
+use alloc::vec::Vec;
+
+use alloy_sol_types::sol;
+use stylus_sdk::{
+    alloy_primitives::{Address, B256},
+    keccak_const::Keccak256,
+    prelude::*,
+};
+
+use crate::Counter;
+
+/// Role that grants and revokes every other role. Held by the owner.
+pub const DEFAULT_ADMIN_ROLE: [u8; 32] = [0; 32];
+
+/// Role allowed to mint swords without a signed drop claim.
+pub const MINTER_ROLE: [u8; 32] = Keccak256::new().update(b"MINTER_ROLE").finalize();
+
+sol! {
+    event RoleGranted(bytes32 indexed role, address indexed account, address indexed sender);
+    event RoleRevoked(bytes32 indexed role, address indexed account, address indexed sender);
+    event OwnershipTransferStarted(address indexed previousOwner, address indexed newOwner);
+    event OwnershipTransferred(address indexed previousOwner, address indexed newOwner);
+
+    error AccessControlUnauthorizedAccount(address account, bytes32 neededRole);
+    error AccessControlBadConfirmation();
+    error OwnableUnauthorizedAccount(address account);
+}
+
+/// Errors returned when the caller isn't allowed to do something.
+#[derive(SolidityError)]
+pub enum AccessError {
+    UnauthorizedAccount(AccessControlUnauthorizedAccount),
+    BadConfirmation(AccessControlBadConfirmation),
+    OwnableUnauthorizedAccount(OwnableUnauthorizedAccount),
+}
+
+/// The external ownership and role management methods.
+pub trait IAccessControl {
+    fn owner(&self) -> Address;
+
+    fn pending_owner(&self) -> Address;
+
+    fn transfer_ownership(&mut self, new_owner: Address) -> Result<(), AccessError>;
+
+    fn accept_ownership(&mut self) -> Result<(), AccessError>;
+
+    fn has_role(&self, role: B256, account: Address) -> bool;
+
+    fn get_role_admin(&self, role: B256) -> B256;
+
+    fn grant_role(&mut self, role: B256, account: Address) -> Result<(), AccessError>;
+
+    fn revoke_role(&mut self, role: B256, account: Address) -> Result<(), AccessError>;
+
+    fn renounce_role(
+        &mut self,
+        role: B256,
+        caller_confirmation: Address,
+    ) -> Result<(), AccessError>;
+}
+
+#[public]
+impl IAccessControl for Counter {
+    /// Gets the current owner.
+    fn owner(&self) -> Address {
+        self.owner.get()
+    }
+
+    /// Gets the account that may accept ownership, if a transfer was started.
+    fn pending_owner(&self) -> Address {
+        self.pending_owner.get()
+    }
+
+    /// Starts handing ownership over to `new_owner`, who must accept it to complete the transfer.
+    fn transfer_ownership(&mut self, new_owner: Address) -> Result<(), AccessError> {
+        self.only_owner()?;
+        self.pending_owner.set(new_owner);
+        log(
+            self.vm(),
+            OwnershipTransferStarted {
+                previousOwner: self.owner.get(),
+                newOwner: new_owner,
+            },
+        );
+        Ok(())
+    }
+
+    /// Completes an ownership transfer. The admin role moves along with ownership.
+    fn accept_ownership(&mut self) -> Result<(), AccessError> {
+        let new_owner = self.vm().msg_sender();
+        if new_owner != self.pending_owner.get() {
+            return Err(AccessError::OwnableUnauthorizedAccount(
+                OwnableUnauthorizedAccount { account: new_owner },
+            ));
+        }
+        let previous_owner = self.owner.get();
+        self.pending_owner.set(Address::ZERO);
+        self.set_owner(previous_owner, new_owner);
+        Ok(())
+    }
+
+    /// Whether `account` holds `role`.
+    fn has_role(&self, role: B256, account: Address) -> bool {
+        self.roles.getter(role).get(account)
+    }
+
+    /// Gets the role that administers `role`.
+    fn get_role_admin(&self, _role: B256) -> B256 {
+        DEFAULT_ADMIN_ROLE.into()
+    }
+
+    /// Gives `role` to `account`. Only callable by holders of the role's admin role.
+    fn grant_role(&mut self, role: B256, account: Address) -> Result<(), AccessError> {
+        self.only_role(self.get_role_admin(role))?;
+        self.grant(role, account);
+        Ok(())
+    }
+
+    /// Takes `role` away from `account`. Only callable by holders of the role's admin role.
+    fn revoke_role(&mut self, role: B256, account: Address) -> Result<(), AccessError> {
+        self.only_role(self.get_role_admin(role))?;
+        self.revoke(role, account);
+        Ok(())
+    }
+
+    /// Gives up one of the caller's own roles. `caller_confirmation` must be the caller's address.
+    fn renounce_role(
+        &mut self,
+        role: B256,
+        caller_confirmation: Address,
+    ) -> Result<(), AccessError> {
+        if caller_confirmation != self.vm().msg_sender() {
+            return Err(AccessError::BadConfirmation(
+                AccessControlBadConfirmation {},
+            ));
+        }
+        self.revoke(role, caller_confirmation);
+        Ok(())
+    }
+}
+
+impl Counter {
+    /// Fails unless the caller holds `role`.
+    pub(crate) fn only_role(&self, role: impl Into<B256>) -> Result<(), AccessError> {
+        let role = role.into();
+        let account = self.vm().msg_sender();
+        if !self.has_role(role, account) {
+            return Err(AccessError::UnauthorizedAccount(
+                AccessControlUnauthorizedAccount {
+                    account,
+                    neededRole: role,
+                },
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fails unless the caller is the owner.
+    pub(crate) fn only_owner(&self) -> Result<(), AccessError> {
+        let account = self.vm().msg_sender();
+        if account != self.owner.get() {
+            return Err(AccessError::OwnableUnauthorizedAccount(
+                OwnableUnauthorizedAccount { account },
+            ));
+        }
+        Ok(())
+    }
+
+    /// Makes `new_owner` the owner and moves the admin role over from `previous_owner`.
+    pub(crate) fn set_owner(&mut self, previous_owner: Address, new_owner: Address) {
+        self.owner.set(new_owner);
+        if !previous_owner.is_zero() {
+            self.revoke(DEFAULT_ADMIN_ROLE.into(), previous_owner);
+        }
+        self.grant(DEFAULT_ADMIN_ROLE.into(), new_owner);
+        log(
+            self.vm(),
+            OwnershipTransferred {
+                previousOwner: previous_owner,
+                newOwner: new_owner,
+            },
+        );
+    }
+
+    /// Gives `role` to `account`, logging it if the account didn't hold it yet.
+    pub(crate) fn grant(&mut self, role: B256, account: Address) {
+        if self.has_role(role, account) {
+            return;
+        }
+        self.roles.setter(role).insert(account, true);
+        log(
+            self.vm(),
+            RoleGranted {
+                role,
+                account,
+                sender: self.vm().msg_sender(),
+            },
+        );
+    }
+
+    /// Takes `role` away from `account`, logging it if the account held it.
+    fn revoke(&mut self, role: B256, account: Address) {
+        if !self.has_role(role, account) {
+            return;
+        }
+        self.roles.setter(role).insert(account, false);
+        log(
+            self.vm(),
+            RoleRevoked {
+                role,
+                account,
+                sender: self.vm().msg_sender(),
+            },
+        );
+    }
+}
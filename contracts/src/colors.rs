@@ -0,0 +1,179 @@
+//!
+//! On-chain registry of sword colors.
+//!
+//! Each color is identified by its index in the registry and has a name, a rarity weight, an
+//! enabled flag and a droppable flag. Only enabled colors can be minted, and only droppable ones
+//! can be collected or claimed as drops, so colors that only come from crafting can't be claimed
+//! directly. The game draws its drops from the registry with probability proportional to each
+//! color's weight, so new sword types don't need a client release.
+//!
+
+use alloc::{string::String, vec::Vec};
+
+use alloy_sol_types::sol;
+use stylus_sdk::{alloy_primitives::U256, prelude::*};
+
+use crate::{
+    access::DEFAULT_ADMIN_ROLE,
+    errors::{CounterError, InvalidColor},
+    Counter,
+};
+
+sol! {
+    event ColorAdded(uint256 indexed id, string name, uint256 weight, bool droppable);
+    event ColorUpdated(uint256 indexed id, string name, uint256 weight, bool enabled);
+    event ColorDroppableChanged(uint256 indexed id, bool droppable);
+
+    error ColorNotDroppable(uint256 color);
+}
+
+/// The external color registry methods.
+pub trait IColorRegistry {
+    fn color_count(&self) -> U256;
+
+    fn get_color(&self, id: U256) -> Result<(String, U256, bool), CounterError>;
+
+    fn get_colors(&self) -> (Vec<String>, Vec<U256>, Vec<bool>);
+
+    fn is_color_droppable(&self, id: U256) -> bool;
+
+    fn add_color(
+        &mut self,
+        name: String,
+        weight: U256,
+        droppable: bool,
+    ) -> Result<U256, CounterError>;
+
+    fn set_color(
+        &mut self,
+        id: U256,
+        name: String,
+        weight: U256,
+        enabled: bool,
+    ) -> Result<(), CounterError>;
+
+    fn set_color_droppable(&mut self, id: U256, droppable: bool) -> Result<(), CounterError>;
+}
+
+#[public]
+impl IColorRegistry for Counter {
+    /// Gets how many colors are registered. Color ids go from zero to this count, exclusive.
+    fn color_count(&self) -> U256 {
+        U256::from(self.colors.len())
+    }
+
+    /// Gets the name, rarity weight and enabled flag of color `id`.
+    fn get_color(&self, id: U256) -> Result<(String, U256, bool), CounterError> {
+        let color = self.colors.getter(id).ok_or(InvalidColor { color: id })?;
+        Ok((
+            color.name.get_string(),
+            color.weight.get(),
+            color.enabled.get(),
+        ))
+    }
+
+    /// Gets the names, rarity weights and enabled flags of every color, indexed by color id.
+    fn get_colors(&self) -> (Vec<String>, Vec<U256>, Vec<bool>) {
+        let mut names = Vec::new();
+        let mut weights = Vec::new();
+        let mut enabled = Vec::new();
+        for id in 0..self.colors.len() {
+            let color = self.colors.getter(id).unwrap();
+            names.push(color.name.get_string());
+            weights.push(color.weight.get());
+            enabled.push(color.enabled.get());
+        }
+        (names, weights, enabled)
+    }
+
+    /// Whether swords of color `id` can be collected as drops: it's registered, enabled and
+    /// droppable.
+    fn is_color_droppable(&self, id: U256) -> bool {
+        self.check_droppable(id).is_ok()
+    }
+
+    /// Registers a new, enabled color and returns its id. Colors that should only come from
+    /// crafting are added with `droppable` unset. Only callable by admins.
+    fn add_color(
+        &mut self,
+        name: String,
+        weight: U256,
+        droppable: bool,
+    ) -> Result<U256, CounterError> {
+        self.only_role(DEFAULT_ADMIN_ROLE)?;
+        Ok(self.register_color(name, weight, droppable))
+    }
+
+    /// Renames, reweighs, enables or disables color `id`. Only callable by admins.
+    fn set_color(
+        &mut self,
+        id: U256,
+        name: String,
+        weight: U256,
+        enabled: bool,
+    ) -> Result<(), CounterError> {
+        self.only_role(DEFAULT_ADMIN_ROLE)?;
+        let mut color = self.colors.setter(id).ok_or(InvalidColor { color: id })?;
+        color.name.set_str(&name);
+        color.weight.set(weight);
+        color.enabled.set(enabled);
+        log(
+            self.vm(),
+            ColorUpdated {
+                id,
+                name,
+                weight,
+                enabled,
+            },
+        );
+        Ok(())
+    }
+
+    /// Sets whether swords of color `id` can be collected as drops. Only callable by admins.
+    fn set_color_droppable(&mut self, id: U256, droppable: bool) -> Result<(), CounterError> {
+        self.only_role(DEFAULT_ADMIN_ROLE)?;
+        let mut color = self.colors.setter(id).ok_or(InvalidColor { color: id })?;
+        color.droppable.set(droppable);
+        log(self.vm(), ColorDroppableChanged { id, droppable });
+        Ok(())
+    }
+}
+
+impl Counter {
+    /// Appends an enabled color to the registry and returns its id.
+    pub(crate) fn register_color(&mut self, name: String, weight: U256, droppable: bool) -> U256 {
+        let id = U256::from(self.colors.len());
+        let mut color = self.colors.grow();
+        color.name.set_str(&name);
+        color.weight.set(weight);
+        color.enabled.set(true);
+        color.droppable.set(droppable);
+        log(
+            self.vm(),
+            ColorAdded {
+                id,
+                name,
+                weight,
+                droppable,
+            },
+        );
+        id
+    }
+
+    /// Fails with `InvalidColor` unless `color` is a registered, enabled color.
+    pub(crate) fn check_color(&self, color: U256) -> Result<(), CounterError> {
+        match self.colors.getter(color) {
+            Some(entry) if entry.enabled.get() => Ok(()),
+            _ => Err(InvalidColor { color }.into()),
+        }
+    }
+    /// Fails with `InvalidColor` unless `color` is a registered, enabled color, and with
+    /// `ColorNotDroppable` unless it can also be collected as a drop.
+    pub(crate) fn check_droppable(&self, color: U256) -> Result<(), CounterError> {
+        self.check_color(color)?;
+        match self.colors.getter(color) {
+            Some(entry) if entry.droppable.get() => Ok(()),
+            _ => Err(ColorNotDroppable { color }.into()),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//!
+//! Sword crafting.
+//!
+//! Admins register recipes that turn a set of input swords into an output sword, for example three
+//! red swords into one crimson sword. `craft` burns the inputs from the caller's inventory and mints
+//! the output in the same transaction, so either the whole recipe goes through or nothing does.
+//!
+
+use alloc::vec::Vec;
+
+use alloy_sol_types::sol;
+use stylus_sdk::{alloy_primitives::U256, prelude::*};
+
+use crate::{access::DEFAULT_ADMIN_ROLE, errors::CounterError, Counter};
+
+sol! {
+    event RecipeAdded(uint256 indexed recipeId, uint256[] inputColors, uint256[] inputAmounts, uint256 outputColor, uint256 outputAmount);
+    event RecipeUpdated(uint256 indexed recipeId, bool enabled);
+    event Crafted(address indexed player, uint256 indexed recipeId, uint256 outputColor, uint256 outputAmount);
+
+    error InvalidRecipe(uint256 recipeId);
+    error InsufficientSwords(uint256 color, uint256 balance, uint256 needed);
+}
+
+/// A recipe as returned by `get_recipe`: input colors, input amounts, output color, output amount
+/// and enabled flag.
+pub type RecipeView = (Vec<U256>, Vec<U256>, U256, U256, bool);
+
+/// The external crafting methods.
+pub trait ICrafting {
+    fn recipe_count(&self) -> U256;
+
+    fn get_recipe(&self, recipe_id: U256) -> Result<RecipeView, CounterError>;
+
+    fn add_recipe(
+        &mut self,
+        input_colors: Vec<U256>,
+        input_amounts: Vec<U256>,
+        output_color: U256,
+        output_amount: U256,
+    ) -> Result<U256, CounterError>;
+
+    fn set_recipe_enabled(&mut self, recipe_id: U256, enabled: bool) -> Result<(), CounterError>;
+
+    fn craft(&mut self, recipe_id: U256) -> Result<(), CounterError>;
+}
+
+#[public]
+impl ICrafting for Counter {
+    /// Gets how many recipes are registered. Recipe ids go from zero to this count, exclusive.
+    fn recipe_count(&self) -> U256 {
+        U256::from(self.recipes.len())
+    }
+
+    /// Gets recipe `recipe_id` as its input colors, input amounts, output color, output amount and
+    /// enabled flag.
+    fn get_recipe(&self, recipe_id: U256) -> Result<RecipeView, CounterError> {
+        let recipe = self.recipes.getter(recipe_id).ok_or(InvalidRecipe {
+            recipeId: recipe_id,
+        })?;
+        let input_colors = (0..recipe.input_colors.len())
+            .filter_map(|i| recipe.input_colors.get(i))
+            .collect();
+        let input_amounts = (0..recipe.input_amounts.len())
+            .filter_map(|i| recipe.input_amounts.get(i))
+            .collect();
+        Ok((
+            input_colors,
+            input_amounts,
+            recipe.output_color.get(),
+            recipe.output_amount.get(),
+            recipe.enabled.get(),
+        ))
+    }
+
+    /// Registers an enabled recipe that burns `input_amounts[i]` swords of `input_colors[i]` and
+    /// mints `output_amount` swords of `output_color`. Each input color may appear only once. Only
+    /// callable by admins.
+    fn add_recipe(
+        &mut self,
+        input_colors: Vec<U256>,
+        input_amounts: Vec<U256>,
+        output_color: U256,
+        output_amount: U256,
+    ) -> Result<U256, CounterError> {
+        self.only_role(DEFAULT_ADMIN_ROLE)?;
+        let recipe_id = U256::from(self.recipes.len());
+        if input_colors.is_empty()
+            || input_colors.len() != input_amounts.len()
+            || output_amount.is_zero()
+            || (1..input_colors.len()).any(|i| input_colors[..i].contains(&input_colors[i]))
+        {
+            return Err(InvalidRecipe {
+                recipeId: recipe_id,
+            }
+            .into());
+        }
+        for &color in input_colors.iter().chain([output_color].iter()) {
+            self.check_color(color)?;
+        }
+
+        let mut recipe = self.recipes.grow();
+        for (&color, &amount) in input_colors.iter().zip(input_amounts.iter()) {
+            recipe.input_colors.push(color);
+            recipe.input_amounts.push(amount);
+        }
+        recipe.output_color.set(output_color);
+        recipe.output_amount.set(output_amount);
+        recipe.enabled.set(true);
+        log(
+            self.vm(),
+            RecipeAdded {
+                recipeId: recipe_id,
+                inputColors: input_colors,
+                inputAmounts: input_amounts,
+                outputColor: output_color,
+                outputAmount: output_amount,
+            },
+        );
+        Ok(recipe_id)
+    }
+
+    /// Enables or disables recipe `recipe_id`. Only callable by admins.
+    fn set_recipe_enabled(&mut self, recipe_id: U256, enabled: bool) -> Result<(), CounterError> {
+        self.only_role(DEFAULT_ADMIN_ROLE)?;
+        let mut recipe = self.recipes.setter(recipe_id).ok_or(InvalidRecipe {
+            recipeId: recipe_id,
+        })?;
+        recipe.enabled.set(enabled);
+        log(
+            self.vm(),
+            RecipeUpdated {
+                recipeId: recipe_id,
+                enabled,
+            },
+        );
+        Ok(())
+    }
+
+    /// Burns the inputs of recipe `recipe_id` from the caller's inventory and mints its output.
+    fn craft(&mut self, recipe_id: U256) -> Result<(), CounterError> {
+        let (input_colors, input_amounts, output_color, output_amount, enabled) =
+            self.get_recipe(recipe_id)?;
+        if !enabled {
+            return Err(InvalidRecipe {
+                recipeId: recipe_id,
+            }
+            .into());
+        }
+
+        let player = self.vm().msg_sender();
+        self.check_color(output_color)?;
+        for (&color, &amount) in input_colors.iter().zip(input_amounts.iter()) {
+            let balance = self.swords.getter(player).get(color);
+            if balance < amount {
+                return Err(InsufficientSwords {
+                    color,
+                    balance,
+                    needed: amount,
+                }
+                .into());
+            }
+        }
+        for (color, amount) in input_colors.into_iter().zip(input_amounts) {
+            self.burn_swords(player, color, amount);
+        }
+        self.mint_swords(player, output_color, output_amount)?;
+        log(
+            self.vm(),
+            Crafted {
+                player,
+                recipeId: recipe_id,
+                outputColor: output_color,
+                outputAmount: output_amount,
+            },
+        );
+        Ok(())
+    }
+}
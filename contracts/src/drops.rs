@@ -0,0 +1,141 @@
+//!
+//! Signed sword drops.
+//!
+//! The drop signing service signs a claim for the swords dropped by a player's kills, and `Counter`
+//! only mints them when the claim carries a valid ECDSA signature from the configured drop signer.
+//! Claims cover one sword or a batch of them, and are bound to the player, the swords, a per-player
+//! nonce and an expiry, so they can't be forged or replayed.
+//!
+
+use alloy_sol_types::{sol, SolValue};
+use stylus_sdk::{
+    alloy_primitives::{address, Address, B256, U256},
+    prelude::*,
+    stylus_core::calls::context::Call,
+};
+
+use crate::{errors::CounterError, Counter};
+
+sol! {
+    error InvalidDropSignature();
+    error DropExpired(uint256 expiry, uint256 timestamp);
+    error InvalidDropNonce(uint256 expected, uint256 provided);
+}
+
+/// The precompile implementing ECDSA public key recovery.
+pub const ECRECOVER: Address = address!("0000000000000000000000000000000000000001");
+
+/// Prefix `eth_sign` and `personal_sign` put in front of a 32-byte message before hashing it.
+const SIGNED_MESSAGE_PREFIX: &[u8] = b"\x19Ethereum Signed Message:\n32";
+
+impl Counter {
+    /// Hashes a drop claim the way the drop signing service signs it: the EIP-191 personal message hash of
+    /// `keccak256(abi.encode(contract, chainId, player, color, nonce, expiry))`.
+    pub(crate) fn drop_claim_hash(
+        &self,
+        player: Address,
+        color: U256,
+        nonce: U256,
+        expiry: U256,
+    ) -> B256 {
+        let claim = (
+            self.vm().contract_address(),
+            U256::from(self.vm().chain_id()),
+            player,
+            color,
+            nonce,
+            expiry,
+        )
+            .abi_encode_params();
+        self.signed_message_hash(&claim)
+    }
+
+    /// Hashes a batch drop claim the way the drop signing service signs it: the EIP-191 personal
+    /// message hash of `keccak256(abi.encode(contract, chainId, player, colors, amounts, nonce, expiry))`.
+    pub(crate) fn drop_batch_hash(
+        &self,
+        player: Address,
+        colors: &[U256],
+        amounts: &[U256],
+        nonce: U256,
+        expiry: U256,
+    ) -> B256 {
+        let claim = (
+            self.vm().contract_address(),
+            U256::from(self.vm().chain_id()),
+            player,
+            colors.to_vec(),
+            amounts.to_vec(),
+            nonce,
+            expiry,
+        )
+            .abi_encode_params();
+        self.signed_message_hash(&claim)
+    }
+
+    /// The EIP-191 personal message hash of `keccak256(claim)`.
+    fn signed_message_hash(&self, claim: &[u8]) -> B256 {
+        let claim_hash = self.vm().native_keccak256(claim);
+
+        let mut message = SIGNED_MESSAGE_PREFIX.to_vec();
+        message.extend_from_slice(claim_hash.as_slice());
+        self.vm().native_keccak256(&message)
+    }
+
+    /// Checks a drop claim for `player`, whose signed message hashes to `hash`, and consumes its nonce.
+    pub(crate) fn verify_drop_claim(
+        &mut self,
+        player: Address,
+        nonce: U256,
+        expiry: U256,
+        hash: B256,
+        signature: &[u8],
+    ) -> Result<(), CounterError> {
+        let timestamp = U256::from(self.vm().block_timestamp());
+        if expiry < timestamp {
+            return Err(CounterError::DropExpired(DropExpired { expiry, timestamp }));
+        }
+
+        let expected = self.drop_nonces.get(player);
+        if nonce != expected {
+            return Err(CounterError::InvalidDropNonce(InvalidDropNonce {
+                expected,
+                provided: nonce,
+            }));
+        }
+
+        let signer = self.drop_signer.get();
+        match self.recover_signer(hash, signature) {
+            Some(recovered) if !signer.is_zero() && recovered == signer => {}
+            _ => return Err(CounterError::InvalidDropSignature(InvalidDropSignature {})),
+        }
+
+        self.drop_nonces.insert(player, nonce + U256::from(1));
+        Ok(())
+    }
+
+    /// Recovers the address that produced a 65-byte `r || s || v` signature over `hash`.
+    fn recover_signer(&self, hash: B256, signature: &[u8]) -> Option<Address> {
+        if signature.len() != 65 {
+            return None;
+        }
+        let v = match signature[64] {
+            v @ (27 | 28) => v,
+            v @ (0 | 1) => v + 27,
+            _ => return None,
+        };
+
+        let mut input = hash.to_vec();
+        input.extend_from_slice(B256::from(U256::from(v)).as_slice());
+        input.extend_from_slice(&signature[..64]);
+
+        let output = self
+            .vm()
+            .static_call(&Call::new(), ECRECOVER, &input)
+            .ok()?;
+        if output.len() != 32 {
+            return None;
+        }
+        Some(Address::from_slice(&output[12..]))
+    }
+}
@@ -0,0 +1,387 @@
+//!
+//! ERC-1155 multi-token interface for swords.
+//!
+//! Every sword color is a token id, and `Counter::swords` doubles as the ERC-1155 balance table,
+//! so wallets and marketplaces can read and move the swords collected in the game.
+//!
+
+use alloc::vec::Vec;
+
+use alloy_sol_types::{sol, SolCall};
+use stylus_sdk::{
+    abi::Bytes,
+    alloy_primitives::{Address, FixedBytes, U256},
+    prelude::*,
+    stylus_core::calls::context::Call,
+};
+
+use crate::{
+    errors::{CounterError, Overflow},
+    Counter,
+};
+
+sol! {
+    event TransferSingle(address indexed operator, address indexed from, address indexed to, uint256 id, uint256 value);
+    event TransferBatch(address indexed operator, address indexed from, address indexed to, uint256[] ids, uint256[] values);
+    event ApprovalForAll(address indexed account, address indexed operator, bool approved);
+
+    error ERC1155InsufficientBalance(address sender, uint256 balance, uint256 needed, uint256 tokenId);
+    error ERC1155InvalidSender(address sender);
+    error ERC1155InvalidReceiver(address receiver);
+    error ERC1155MissingApprovalForAll(address operator, address owner);
+    error ERC1155InvalidOperator(address operator);
+    error ERC1155InvalidArrayLength(uint256 idsLength, uint256 valuesLength);
+
+    interface IERC1155Receiver {
+        function onERC1155Received(address operator, address from, uint256 id, uint256 value, bytes data) external returns (bytes4);
+        function onERC1155BatchReceived(address operator, address from, uint256[] ids, uint256[] values, bytes data) external returns (bytes4);
+    }
+}
+
+/// ERC-165 identifier of ERC-165 itself.
+const ERC165_INTERFACE_ID: [u8; 4] = [0x01, 0xff, 0xc9, 0xa7];
+
+/// ERC-165 identifier of ERC-1155.
+const ERC1155_INTERFACE_ID: [u8; 4] = [0xd9, 0xb6, 0x7a, 0x26];
+
+/// Errors returned by the ERC-1155 entrypoints, ABI-encoded as the standard's custom errors plus
+/// `Overflow` for balances that would wrap around.
+#[derive(SolidityError)]
+pub enum Erc1155Error {
+    InsufficientBalance(ERC1155InsufficientBalance),
+    InvalidSender(ERC1155InvalidSender),
+    InvalidReceiver(ERC1155InvalidReceiver),
+    MissingApprovalForAll(ERC1155MissingApprovalForAll),
+    InvalidOperator(ERC1155InvalidOperator),
+    InvalidArrayLength(ERC1155InvalidArrayLength),
+    Overflow(Overflow),
+}
+
+/// The external ERC-1155 methods, exposed with their Solidity names.
+pub trait IErc1155 {
+    fn balance_of(&self, account: Address, id: U256) -> U256;
+
+    fn balance_of_batch(
+        &self,
+        accounts: Vec<Address>,
+        ids: Vec<U256>,
+    ) -> Result<Vec<U256>, Erc1155Error>;
+
+    fn set_approval_for_all(
+        &mut self,
+        operator: Address,
+        approved: bool,
+    ) -> Result<(), Erc1155Error>;
+
+    fn is_approved_for_all(&self, account: Address, operator: Address) -> bool;
+
+    fn safe_transfer_from(
+        &mut self,
+        from: Address,
+        to: Address,
+        id: U256,
+        value: U256,
+        data: Bytes,
+    ) -> Result<(), Erc1155Error>;
+
+    fn safe_batch_transfer_from(
+        &mut self,
+        from: Address,
+        to: Address,
+        ids: Vec<U256>,
+        values: Vec<U256>,
+        data: Bytes,
+    ) -> Result<(), Erc1155Error>;
+
+    fn supports_interface(&self, interface_id: FixedBytes<4>) -> bool;
+}
+
+#[public]
+impl IErc1155 for Counter {
+    /// Gets how many swords of color `id` `account` owns.
+    fn balance_of(&self, account: Address, id: U256) -> U256 {
+        self.swords.getter(account).get(id)
+    }
+
+    /// Gets the balance of each `(accounts[i], ids[i])` pair.
+    fn balance_of_batch(
+        &self,
+        accounts: Vec<Address>,
+        ids: Vec<U256>,
+    ) -> Result<Vec<U256>, Erc1155Error> {
+        if accounts.len() != ids.len() {
+            return Err(Erc1155Error::InvalidArrayLength(
+                ERC1155InvalidArrayLength {
+                    idsLength: U256::from(ids.len()),
+                    valuesLength: U256::from(accounts.len()),
+                },
+            ));
+        }
+        Ok(accounts
+            .into_iter()
+            .zip(ids)
+            .map(|(account, id)| self.balance_of(account, id))
+            .collect())
+    }
+
+    /// Lets `operator` move all of the caller's swords, or revokes that permission.
+    fn set_approval_for_all(
+        &mut self,
+        operator: Address,
+        approved: bool,
+    ) -> Result<(), Erc1155Error> {
+        if operator.is_zero() {
+            return Err(Erc1155Error::InvalidOperator(ERC1155InvalidOperator {
+                operator,
+            }));
+        }
+        let account = self.vm().msg_sender();
+        self.operator_approvals
+            .setter(account)
+            .insert(operator, approved);
+        log(
+            self.vm(),
+            ApprovalForAll {
+                account,
+                operator,
+                approved,
+            },
+        );
+        Ok(())
+    }
+
+    /// Whether `operator` may move all of `account`'s swords.
+    fn is_approved_for_all(&self, account: Address, operator: Address) -> bool {
+        self.operator_approvals.getter(account).get(operator)
+    }
+
+    /// Moves `value` swords of color `id` from `from` to `to`.
+    fn safe_transfer_from(
+        &mut self,
+        from: Address,
+        to: Address,
+        id: U256,
+        value: U256,
+        data: Bytes,
+    ) -> Result<(), Erc1155Error> {
+        let operator = self.vm().msg_sender();
+        self.check_operator(operator, from)?;
+        self.check_endpoints(from, to)?;
+        self.move_swords(from, to, id, value)?;
+        log(
+            self.vm(),
+            TransferSingle {
+                operator,
+                from,
+                to,
+                id,
+                value,
+            },
+        );
+        self.check_on_received(operator, from, to, id, value, data)?;
+        Ok(())
+    }
+
+    /// Moves `values[i]` swords of color `ids[i]` from `from` to `to`, for every `i`.
+    fn safe_batch_transfer_from(
+        &mut self,
+        from: Address,
+        to: Address,
+        ids: Vec<U256>,
+        values: Vec<U256>,
+        data: Bytes,
+    ) -> Result<(), Erc1155Error> {
+        if ids.len() != values.len() {
+            return Err(Erc1155Error::InvalidArrayLength(
+                ERC1155InvalidArrayLength {
+                    idsLength: U256::from(ids.len()),
+                    valuesLength: U256::from(values.len()),
+                },
+            ));
+        }
+        let operator = self.vm().msg_sender();
+        self.check_operator(operator, from)?;
+        self.check_endpoints(from, to)?;
+        for (&id, &value) in ids.iter().zip(values.iter()) {
+            self.move_swords(from, to, id, value)?;
+        }
+        log(
+            self.vm(),
+            TransferBatch {
+                operator,
+                from,
+                to,
+                ids: ids.clone(),
+                values: values.clone(),
+            },
+        );
+        self.check_on_batch_received(operator, from, to, ids, values, data)?;
+        Ok(())
+    }
+
+    /// ERC-165 interface detection.
+    fn supports_interface(&self, interface_id: FixedBytes<4>) -> bool {
+        interface_id == ERC165_INTERFACE_ID || interface_id == ERC1155_INTERFACE_ID
+    }
+}
+
+impl Counter {
+    /// Adds `value` swords of color `id` to `to` and logs it as a mint. Contract recipients have to
+    /// acknowledge it like any other transfer.
+    pub(crate) fn mint_swords(
+        &mut self,
+        to: Address,
+        id: U256,
+        value: U256,
+    ) -> Result<(), CounterError> {
+        let operator = self.vm().msg_sender();
+        let mut inventory = self.swords.setter(to);
+        let balance = inventory.get(id);
+        inventory.insert(id, balance.checked_add(value).ok_or(Overflow {})?);
+        log(
+            self.vm(),
+            TransferSingle {
+                operator,
+                from: Address::ZERO,
+                to,
+                id,
+                value,
+            },
+        );
+        self.check_on_received(operator, Address::ZERO, to, id, value, Bytes(Vec::new()))?;
+        Ok(())
+    }
+
+    /// Takes `value` swords of color `id` away from `from` and logs it as a burn. The caller must
+    /// have checked the balance.
+    pub(crate) fn burn_swords(&mut self, from: Address, id: U256, value: U256) {
+        let mut inventory = self.swords.setter(from);
+        let balance = inventory.get(id);
+        inventory.insert(id, balance - value);
+        log(
+            self.vm(),
+            TransferSingle {
+                operator: self.vm().msg_sender(),
+                from,
+                to: Address::ZERO,
+                id,
+                value,
+            },
+        );
+    }
+
+    fn check_operator(&self, operator: Address, owner: Address) -> Result<(), Erc1155Error> {
+        if operator != owner && !self.is_approved_for_all(owner, operator) {
+            return Err(Erc1155Error::MissingApprovalForAll(
+                ERC1155MissingApprovalForAll { operator, owner },
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_endpoints(&self, from: Address, to: Address) -> Result<(), Erc1155Error> {
+        if from.is_zero() {
+            return Err(Erc1155Error::InvalidSender(ERC1155InvalidSender {
+                sender: from,
+            }));
+        }
+        if to.is_zero() {
+            return Err(Erc1155Error::InvalidReceiver(ERC1155InvalidReceiver {
+                receiver: to,
+            }));
+        }
+        Ok(())
+    }
+
+    fn move_swords(
+        &mut self,
+        from: Address,
+        to: Address,
+        id: U256,
+        value: U256,
+    ) -> Result<(), Erc1155Error> {
+        let mut sender = self.swords.setter(from);
+        let balance = sender.get(id);
+        if balance < value {
+            return Err(Erc1155Error::InsufficientBalance(
+                ERC1155InsufficientBalance {
+                    sender: from,
+                    balance,
+                    needed: value,
+                    tokenId: id,
+                },
+            ));
+        }
+        sender.insert(id, balance - value);
+
+        let mut receiver = self.swords.setter(to);
+        let balance = receiver.get(id);
+        receiver.insert(id, balance.checked_add(value).ok_or(Overflow {})?);
+        Ok(())
+    }
+
+    /// Requires contract recipients to acknowledge a single transfer, as ERC-1155 mandates.
+    fn check_on_received(
+        &mut self,
+        operator: Address,
+        from: Address,
+        to: Address,
+        id: U256,
+        value: U256,
+        data: Bytes,
+    ) -> Result<(), ERC1155InvalidReceiver> {
+        if self.vm().code_size(to) == 0 {
+            return Ok(());
+        }
+        let call = IERC1155Receiver::onERC1155ReceivedCall {
+            operator,
+            from,
+            id,
+            value,
+            data: data.0.into(),
+        };
+        self.check_receiver_reply(to, &call.abi_encode(), |reply| {
+            IERC1155Receiver::onERC1155ReceivedCall::abi_decode_returns(reply, true)
+                .map(|ret| ret._0 == IERC1155Receiver::onERC1155ReceivedCall::SELECTOR)
+        })
+    }
+
+    /// Requires contract recipients to acknowledge a batch transfer, as ERC-1155 mandates.
+    fn check_on_batch_received(
+        &mut self,
+        operator: Address,
+        from: Address,
+        to: Address,
+        ids: Vec<U256>,
+        values: Vec<U256>,
+        data: Bytes,
+    ) -> Result<(), ERC1155InvalidReceiver> {
+        if self.vm().code_size(to) == 0 {
+            return Ok(());
+        }
+        let call = IERC1155Receiver::onERC1155BatchReceivedCall {
+            operator,
+            from,
+            ids,
+            values,
+            data: data.0.into(),
+        };
+        self.check_receiver_reply(to, &call.abi_encode(), |reply| {
+            IERC1155Receiver::onERC1155BatchReceivedCall::abi_decode_returns(reply, true)
+                .map(|ret| ret._0 == IERC1155Receiver::onERC1155BatchReceivedCall::SELECTOR)
+        })
+    }
+
+    fn check_receiver_reply(
+        &mut self,
+        to: Address,
+        calldata: &[u8],
+        accepted: impl FnOnce(&[u8]) -> alloy_sol_types::Result<bool>,
+    ) -> Result<(), ERC1155InvalidReceiver> {
+        let reply = self.vm().call(&Call::new(), to, calldata);
+        match reply.map(|reply| accepted(&reply)) {
+            Ok(Ok(true)) => Ok(()),
+            _ => Err(ERC1155InvalidReceiver { receiver: to }),
+        }
+    }
+}
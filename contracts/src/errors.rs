@@ -0,0 +1,55 @@
+//!
+//! Errors returned by the game-facing `Counter` methods.
+//!
+//! Every failure reverts with an ABI-encoded Solidity custom error instead of panicking, so
+//! callers get revert data they can decode into something readable.
+//!
+
+use alloy_sol_types::sol;
+use stylus_sdk::prelude::*;
+
+use crate::access::{
+    AccessControlBadConfirmation, AccessControlUnauthorizedAccount, AccessError,
+    OwnableUnauthorizedAccount,
+};
+use crate::colors::ColorNotDroppable;
+use crate::crafting::{InsufficientSwords, InvalidRecipe};
+use crate::drops::{DropExpired, InvalidDropNonce, InvalidDropSignature};
+use crate::erc1155::ERC1155InvalidReceiver;
+
+sol! {
+    error InvalidColor(uint256 color);
+    error Overflow();
+    error InvalidArrayLength(uint256 colorsLength, uint256 amountsLength);
+}
+
+/// Errors returned by `Counter`'s own entrypoints.
+#[derive(SolidityError)]
+pub enum CounterError {
+    InvalidColor(InvalidColor),
+    Overflow(Overflow),
+    UnauthorizedAccount(AccessControlUnauthorizedAccount),
+    BadConfirmation(AccessControlBadConfirmation),
+    OwnableUnauthorizedAccount(OwnableUnauthorizedAccount),
+    InvalidArrayLength(InvalidArrayLength),
+    InvalidDropSignature(InvalidDropSignature),
+    DropExpired(DropExpired),
+    InvalidDropNonce(InvalidDropNonce),
+    InvalidRecipe(InvalidRecipe),
+    InsufficientSwords(InsufficientSwords),
+    ColorNotDroppable(ColorNotDroppable),
+    InvalidReceiver(ERC1155InvalidReceiver),
+}
+
+/// Lets entrypoints check access with `only_role` and return its errors unchanged.
+impl From<AccessError> for CounterError {
+    fn from(error: AccessError) -> Self {
+        match error {
+            AccessError::UnauthorizedAccount(e) => CounterError::UnauthorizedAccount(e),
+            AccessError::BadConfirmation(e) => CounterError::BadConfirmation(e),
+            AccessError::OwnableUnauthorizedAccount(e) => {
+                CounterError::OwnableUnauthorizedAccount(e)
+            }
+        }
+    }
+}
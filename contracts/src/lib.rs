@@ -29,8 +29,33 @@ extern crate alloc;
 
 use alloc::vec::Vec;
 
+pub mod access;
+pub mod colors;
+pub mod crafting;
+pub mod drops;
+pub mod erc1155;
+pub mod errors;
+
 /// Import items from the SDK. The prelude contains common traits and macros.
-use stylus_sdk::{alloy_primitives::U256, prelude::*};
+use alloy_sol_types::sol;
+use stylus_sdk::{
+    abi::Bytes,
+    alloy_primitives::{Address, U256},
+    prelude::*,
+};
+
+use crate::access::{IAccessControl, DEFAULT_ADMIN_ROLE, MINTER_ROLE};
+use crate::colors::IColorRegistry;
+use crate::crafting::ICrafting;
+use crate::erc1155::IErc1155;
+use crate::errors::{CounterError, InvalidArrayLength, Overflow};
+
+// Events logged by `Counter`, so off-chain tools can follow it without polling.
+sol! {
+    event NumberChanged(uint256 oldNumber, uint256 newNumber);
+    event SwordCollected(address indexed player, uint256 indexed color, uint256 newCount);
+    event DropSignerChanged(address indexed previousSigner, address indexed newSigner);
+}
 
 // Define some persistent storage using the Solidity ABI.
 // `Counter` will be the entrypoint.
@@ -38,55 +63,1171 @@ sol_storage! {
     #[entrypoint]
     pub struct Counter {
         uint256 number;
-        mapping(uint256 => uint256) swords;
+        mapping(address => mapping(uint256 => uint256)) swords;
+        mapping(address => mapping(address => bool)) operator_approvals;
+        address drop_signer;
+        mapping(address => uint256) drop_nonces;
+        address owner;
+        address pending_owner;
+        mapping(bytes32 => mapping(address => bool)) roles;
+        SwordColor[] colors;
+        Recipe[] recipes;
+    }
+
+    pub struct SwordColor {
+        string name;
+        uint256 weight;
+        bool enabled;
+        bool droppable;
+    }
+
+    pub struct Recipe {
+        uint256[] input_colors;
+        uint256[] input_amounts;
+        uint256 output_color;
+        uint256 output_amount;
+        bool enabled;
     }
 }
 
 /// Declare that `Counter` is a contract with the following external methods.
 #[public]
+#[implements(IErc1155, IAccessControl, IColorRegistry, ICrafting)]
 impl Counter {
+    /// Makes the deployer the owner, lets them mint swords directly and registers the original
+    /// red, blue and green swords.
+    #[constructor]
+    pub fn constructor(&mut self) {
+        let owner = self.vm().tx_origin();
+        self.set_owner(Address::ZERO, owner);
+        self.grant(MINTER_ROLE.into(), owner);
+        for name in ["red", "blue", "green"] {
+            self.register_color(name.into(), U256::from(1), true);
+        }
+    }
+
     /// Gets the number from storage.
     pub fn number(&self) -> U256 {
         self.number.get()
     }
 
-    /// Sets a number in storage to a user-specified value.
-    pub fn set_number(&mut self, new_number: U256) {
-        self.number.set(new_number);
+    /// Sets a number in storage to a user-specified value. Only callable by admins.
+    pub fn set_number(&mut self, new_number: U256) -> Result<(), CounterError> {
+        self.only_role(DEFAULT_ADMIN_ROLE)?;
+        self.update_number(new_number);
+        Ok(())
     }
 
     /// Increments `number` and updates its value in storage.
-    pub fn increment(&mut self) {
+    pub fn increment(&mut self) -> Result<(), CounterError> {
         let number = self.number.get();
-        self.set_number(number + U256::from(1));
+        let new_number = number.checked_add(U256::from(1)).ok_or(Overflow {})?;
+        self.update_number(new_number);
+        Ok(())
     }
 
+    /// Gets how many swords of `color` the caller owns.
     pub fn get_sword_count(&self, color: U256) -> U256 {
-        self.swords.get(color)
+        self.get_sword_count_of(self.vm().msg_sender(), color)
+    }
+
+    /// Gets how many swords of `color` `player` owns.
+    pub fn get_sword_count_of(&self, player: Address, color: U256) -> U256 {
+        self.swords.getter(player).get(color)
+    }
+
+    /// Gets the caller's red, blue and green sword counts in a single call.
+    pub fn get_sword_counts(&self) -> (U256, U256, U256) {
+        self.get_sword_counts_of(self.vm().msg_sender())
+    }
+
+    /// Gets `player`'s red, blue and green sword counts in a single call.
+    pub fn get_sword_counts_of(&self, player: Address) -> (U256, U256, U256) {
+        let inventory = self.swords.getter(player);
+        (
+            inventory.get(U256::from(0)),
+            inventory.get(U256::from(1)),
+            inventory.get(U256::from(2)),
+        )
+    }
+
+    /// Gets `player`'s count for each of `colors`, in the same order.
+    pub fn get_sword_counts_batch(&self, player: Address, colors: Vec<U256>) -> Vec<U256> {
+        let inventory = self.swords.getter(player);
+        colors
+            .into_iter()
+            .map(|color| inventory.get(color))
+            .collect()
+    }
+
+    /// Adds one sword of `color` to the caller's inventory. Only callable by minters; players
+    /// collect their swords with `claim_sword`.
+    pub fn increment_sword(&mut self, color: U256) -> Result<(), CounterError> {
+        self.only_role(MINTER_ROLE)?;
+        let player = self.vm().msg_sender();
+        self.collect_swords(player, color, U256::from(1))
+    }
+
+    /// Adds `amounts[i]` swords of `colors[i]` to the caller's inventory, for every `i`, so a whole
+    /// play session can be saved in one transaction. Only callable by minters.
+    pub fn increment_swords(
+        &mut self,
+        colors: Vec<U256>,
+        amounts: Vec<U256>,
+    ) -> Result<(), CounterError> {
+        self.only_role(MINTER_ROLE)?;
+        let player = self.vm().msg_sender();
+        self.collect_sword_batch(player, colors, amounts)
+    }
+
+    /// Gets the address whose signatures authorize sword drops.
+    pub fn drop_signer(&self) -> Address {
+        self.drop_signer.get()
     }
 
-    pub fn increment_sword(&mut self, color: U256) {
-        let count = self.get_sword_count(color);
-        self.swords.insert(color, count + U256::from(1));
+    /// Sets the drop signer. Only callable by admins.
+    pub fn set_drop_signer(&mut self, signer: Address) -> Result<(), CounterError> {
+        self.only_role(DEFAULT_ADMIN_ROLE)?;
+        let previous_signer = self.drop_signer.get();
+        self.drop_signer.set(signer);
+        log(
+            self.vm(),
+            DropSignerChanged {
+                previousSigner: previous_signer,
+                newSigner: signer,
+            },
+        );
+        Ok(())
+    }
+
+    /// Gets the nonce `player`'s next drop claim must use.
+    pub fn drop_nonce(&self, player: Address) -> U256 {
+        self.drop_nonces.get(player)
+    }
+
+    /// Mints one sword of `color` to the caller, given a drop claim for it signed by the drop
+    /// signer. `signature` is the 65-byte `r || s || v` signature over the claim.
+    pub fn claim_sword(
+        &mut self,
+        color: U256,
+        nonce: U256,
+        expiry: U256,
+        signature: Bytes,
+    ) -> Result<(), CounterError> {
+        let player = self.vm().msg_sender();
+        let hash = self.drop_claim_hash(player, color, nonce, expiry);
+        self.verify_drop_claim(player, nonce, expiry, hash, &signature)?;
+        self.collect_swords(player, color, U256::from(1))
+    }
+
+    /// Mints `amounts[i]` swords of `colors[i]` to the caller, for every `i`, given a batch drop
+    /// claim for them signed by the drop signer, so players can save a play session in one
+    /// transaction. `signature` is the 65-byte `r || s || v` signature over the claim.
+    pub fn claim_swords(
+        &mut self,
+        colors: Vec<U256>,
+        amounts: Vec<U256>,
+        nonce: U256,
+        expiry: U256,
+        signature: Bytes,
+    ) -> Result<(), CounterError> {
+        let player = self.vm().msg_sender();
+        let hash = self.drop_batch_hash(player, &colors, &amounts, nonce, expiry);
+        self.verify_drop_claim(player, nonce, expiry, hash, &signature)?;
+        self.collect_sword_batch(player, colors, amounts)
+    }
+}
+
+impl Counter {
+    fn update_number(&mut self, new_number: U256) {
+        let old_number = self.number.get();
+        self.number.set(new_number);
+        log(
+            self.vm(),
+            NumberChanged {
+                oldNumber: old_number,
+                newNumber: new_number,
+            },
+        );
+    }
+
+    /// Mints `amounts[i]` swords of `colors[i]` to `player`, for every `i`, after checking every
+    /// color can drop, so a bad entry doesn't leave the batch half minted.
+    fn collect_sword_batch(
+        &mut self,
+        player: Address,
+        colors: Vec<U256>,
+        amounts: Vec<U256>,
+    ) -> Result<(), CounterError> {
+        if colors.len() != amounts.len() {
+            return Err(InvalidArrayLength {
+                colorsLength: U256::from(colors.len()),
+                amountsLength: U256::from(amounts.len()),
+            }
+            .into());
+        }
+        for &color in &colors {
+            self.check_droppable(color)?;
+        }
+        for (color, amount) in colors.into_iter().zip(amounts) {
+            if !amount.is_zero() {
+                self.collect_swords(player, color, amount)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Mints `amount` swords of `color` to `player` for picking them up in the game, as long as
+    /// `color` can drop.
+    fn collect_swords(
+        &mut self,
+        player: Address,
+        color: U256,
+        amount: U256,
+    ) -> Result<(), CounterError> {
+        self.check_droppable(color)?;
+        self.mint_swords(player, color, amount)?;
+        log(
+            self.vm(),
+            SwordCollected {
+                player,
+                color,
+                newCount: self.get_sword_count_of(player, color),
+            },
+        );
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::access::{AccessControlUnauthorizedAccount, AccessError};
+    use crate::errors::InvalidColor;
+    use stylus_sdk::{alloy_primitives::B256, testing::TestVM};
+
+    const OWNER: Address = Address::new([0xaa; 20]);
+
+    /// Deploys `Counter` from `OWNER` and lets each of `minters` mint swords directly.
+    fn deploy(vm: &TestVM, minters: &[Address]) -> Counter {
+        vm.set_tx_origin(OWNER);
+        vm.set_sender(OWNER);
+        let mut contract = Counter::from(vm);
+        contract.constructor();
+        for &minter in minters {
+            assert!(contract.grant_role(MINTER_ROLE.into(), minter).is_ok());
+        }
+        contract
+    }
 
     #[test]
     fn test_counter() {
-        use stylus_sdk::testing::*;
         let vm = TestVM::default();
-        let mut contract = Counter::from(&vm);
+        let mut contract = deploy(&vm, &[]);
 
         assert_eq!(U256::ZERO, contract.number());
 
-        contract.increment();
+        assert!(contract.increment().is_ok());
         assert_eq!(U256::from(1), contract.number());
 
-        contract.set_number(U256::from(100));
+        assert!(contract.set_number(U256::from(100)).is_ok());
         assert_eq!(U256::from(100), contract.number());
+
+        vm.set_sender(Address::from([1u8; 20]));
+        assert!(matches!(
+            contract.set_number(U256::from(7)),
+            Err(CounterError::UnauthorizedAccount(_))
+        ));
+        assert!(contract.increment().is_ok());
+        assert_eq!(U256::from(101), contract.number());
+    }
+
+    #[test]
+    fn test_swords_are_per_player() {
+        let vm = TestVM::default();
+        let alice = Address::from([1u8; 20]);
+        let bob = Address::from([2u8; 20]);
+        let mut contract = deploy(&vm, &[alice, bob]);
+
+        vm.set_sender(alice);
+        assert!(contract.increment_sword(U256::from(0)).is_ok());
+        assert!(contract.increment_sword(U256::from(0)).is_ok());
+        assert!(contract.increment_sword(U256::from(2)).is_ok());
+
+        vm.set_sender(bob);
+        assert!(contract.increment_sword(U256::from(0)).is_ok());
+
+        assert_eq!(U256::from(1), contract.get_sword_count(U256::from(0)));
+        assert_eq!(U256::ZERO, contract.get_sword_count(U256::from(2)));
+
+        assert_eq!(
+            U256::from(2),
+            contract.get_sword_count_of(alice, U256::from(0))
+        );
+        assert_eq!(
+            U256::from(1),
+            contract.get_sword_count_of(alice, U256::from(2))
+        );
+        assert_eq!(
+            U256::from(1),
+            contract.get_sword_count_of(bob, U256::from(0))
+        );
+    }
+
+    #[test]
+    fn test_sword_counts_batch() {
+        let vm = TestVM::default();
+        let player = Address::from([1u8; 20]);
+        let mut contract = deploy(&vm, &[player]);
+        vm.set_sender(player);
+        assert!(contract.increment_sword(U256::from(0)).is_ok());
+        assert!(contract.increment_sword(U256::from(2)).is_ok());
+        assert!(contract.increment_sword(U256::from(2)).is_ok());
+        assert!(matches!(
+            contract.increment_sword(U256::from(5)),
+            Err(CounterError::InvalidColor(_))
+        ));
+
+        let expected = (U256::from(1), U256::ZERO, U256::from(2));
+        assert_eq!(expected, contract.get_sword_counts());
+        assert_eq!(expected, contract.get_sword_counts_of(player));
+
+        let colors = vec![U256::from(5), U256::from(1), U256::from(2)];
+        assert_eq!(
+            vec![U256::ZERO, U256::ZERO, U256::from(2)],
+            contract.get_sword_counts_batch(player, colors)
+        );
+        assert!(contract
+            .get_sword_counts_batch(player, Vec::new())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_erc1155_transfers() {
+        use crate::erc1155::Erc1155Error;
+        let vm = TestVM::default();
+        let alice = Address::from([1u8; 20]);
+        let bob = Address::from([2u8; 20]);
+        let mut contract = deploy(&vm, &[alice]);
+        let red = U256::from(0);
+        let green = U256::from(2);
+
+        vm.set_sender(alice);
+        assert!(contract.increment_sword(red).is_ok());
+        assert!(contract.increment_sword(red).is_ok());
+        assert!(contract.increment_sword(green).is_ok());
+        assert_eq!(U256::from(2), contract.balance_of(alice, red));
+
+        assert!(contract
+            .safe_transfer_from(alice, bob, red, U256::from(1), Bytes(Vec::new()))
+            .is_ok());
+        assert_eq!(U256::from(1), contract.balance_of(alice, red));
+        assert_eq!(U256::from(1), contract.balance_of(bob, red));
+
+        assert!(matches!(
+            contract.safe_transfer_from(alice, bob, red, U256::from(5), Bytes(Vec::new())),
+            Err(Erc1155Error::InsufficientBalance(_))
+        ));
+        assert!(matches!(
+            contract.safe_transfer_from(
+                alice,
+                Address::ZERO,
+                red,
+                U256::from(1),
+                Bytes(Vec::new())
+            ),
+            Err(Erc1155Error::InvalidReceiver(_))
+        ));
+
+        assert!(contract
+            .safe_batch_transfer_from(
+                alice,
+                bob,
+                vec![red, green],
+                vec![U256::from(1), U256::from(1)],
+                Bytes(Vec::new()),
+            )
+            .is_ok());
+        let balances = contract
+            .balance_of_batch(vec![alice, bob, bob], vec![red, red, green])
+            .ok()
+            .unwrap();
+        assert_eq!(vec![U256::ZERO, U256::from(2), U256::from(1)], balances);
+
+        assert!(matches!(
+            contract.balance_of_batch(vec![alice], vec![red, green]),
+            Err(Erc1155Error::InvalidArrayLength(_))
+        ));
+    }
+
+    #[test]
+    fn test_erc1155_operator_approval() {
+        use crate::erc1155::Erc1155Error;
+        let vm = TestVM::default();
+        let alice = Address::from([1u8; 20]);
+        let operator = Address::from([3u8; 20]);
+        let mut contract = deploy(&vm, &[alice]);
+        let blue = U256::from(1);
+
+        vm.set_sender(alice);
+        assert!(contract.increment_sword(blue).is_ok());
+
+        vm.set_sender(operator);
+        assert!(matches!(
+            contract.safe_transfer_from(alice, operator, blue, U256::from(1), Bytes(Vec::new())),
+            Err(Erc1155Error::MissingApprovalForAll(_))
+        ));
+
+        vm.set_sender(alice);
+        assert!(contract.set_approval_for_all(operator, true).is_ok());
+        assert!(contract.is_approved_for_all(alice, operator));
+
+        vm.set_sender(operator);
+        assert!(contract
+            .safe_transfer_from(alice, operator, blue, U256::from(1), Bytes(Vec::new()))
+            .is_ok());
+        assert_eq!(U256::from(1), contract.balance_of(operator, blue));
+    }
+
+    #[test]
+    fn test_erc1155_receiver_hook() {
+        use crate::erc1155::{Erc1155Error, IERC1155Receiver};
+        use alloy_sol_types::SolCall;
+        use stylus_sdk::alloy_primitives::FixedBytes;
+        let vm = TestVM::default();
+        let alice = Address::from([1u8; 20]);
+        let mut contract = deploy(&vm, &[alice]);
+        let receiver = Address::from([4u8; 20]);
+        let silent = Address::from([5u8; 20]);
+        let red = U256::from(0);
+
+        vm.set_sender(alice);
+        assert!(contract.increment_sword(red).is_ok());
+        assert!(contract.increment_sword(red).is_ok());
+        vm.set_code(receiver, vec![0xfe]);
+        vm.set_code(silent, vec![0xfe]);
+
+        let hook = IERC1155Receiver::onERC1155ReceivedCall {
+            operator: alice,
+            from: alice,
+            id: red,
+            value: U256::from(1),
+            data: Default::default(),
+        };
+        let accepted = IERC1155Receiver::onERC1155ReceivedCall::abi_encode_returns(&(
+            FixedBytes::<4>::from(IERC1155Receiver::onERC1155ReceivedCall::SELECTOR),
+        ));
+        vm.mock_call(receiver, hook.abi_encode(), Ok(accepted));
+        assert!(contract
+            .safe_transfer_from(alice, receiver, red, U256::from(1), Bytes(Vec::new()))
+            .is_ok());
+        assert_eq!(U256::from(1), contract.balance_of(receiver, red));
+
+        // A contract that doesn't answer the hook can't receive swords.
+        assert!(matches!(
+            contract.safe_transfer_from(alice, silent, red, U256::from(1), Bytes(Vec::new())),
+            Err(Erc1155Error::InvalidReceiver(_))
+        ));
+    }
+
+    #[test]
+    fn test_mint_receiver_hook() {
+        use crate::erc1155::IERC1155Receiver;
+        use alloy_sol_types::SolCall;
+        use stylus_sdk::alloy_primitives::FixedBytes;
+        let vm = TestVM::default();
+        let vault = Address::from([4u8; 20]);
+        let rejecting = Address::from([5u8; 20]);
+        let mut contract = deploy(&vm, &[vault, rejecting]);
+        let red = U256::from(0);
+        vm.set_code(vault, vec![0xfe]);
+        vm.set_code(rejecting, vec![0xfe]);
+
+        // Mints reach contracts that acknowledge them, as a transfer from the zero address.
+        let hook = |to: Address| IERC1155Receiver::onERC1155ReceivedCall {
+            operator: to,
+            from: Address::ZERO,
+            id: red,
+            value: U256::from(1),
+            data: Default::default(),
+        };
+        let accepted = IERC1155Receiver::onERC1155ReceivedCall::abi_encode_returns(&(
+            FixedBytes::<4>::from(IERC1155Receiver::onERC1155ReceivedCall::SELECTOR),
+        ));
+        vm.mock_call(vault, hook(vault).abi_encode(), Ok(accepted));
+        vm.set_sender(vault);
+        assert!(contract.increment_sword(red).is_ok());
+        assert_eq!(U256::from(1), contract.balance_of(vault, red));
+
+        // A contract that answers with anything but the hook's selector rejects them.
+        let rejected = IERC1155Receiver::onERC1155ReceivedCall::abi_encode_returns(&(
+            FixedBytes::<4>::from([0xde, 0xad, 0xbe, 0xef]),
+        ));
+        vm.mock_call(rejecting, hook(rejecting).abi_encode(), Ok(rejected));
+        vm.set_sender(rejecting);
+        assert!(matches!(
+            contract.increment_sword(red),
+            Err(CounterError::InvalidReceiver(_))
+        ));
+    }
+
+    #[test]
+    fn test_signed_drops() {
+        use crate::drops::ECRECOVER;
+        let vm = TestVM::default();
+        let mut contract = deploy(&vm, &[]);
+
+        let server = Address::from([9u8; 20]);
+        let player = Address::from([1u8; 20]);
+        let blue = U256::from(1);
+        let expiry = U256::from(1_000);
+        vm.set_block_timestamp(500);
+
+        assert!(contract.set_drop_signer(server).is_ok());
+        assert_eq!(server, contract.drop_signer());
+
+        // Fake signature; the ecrecover precompile is mocked to attribute it to `server`.
+        let mut signature = vec![0x11; 32];
+        signature.extend_from_slice(&[0x22; 32]);
+        signature.push(27);
+        let hash = contract.drop_claim_hash(player, blue, U256::ZERO, expiry);
+        let mut input = hash.to_vec();
+        input.extend_from_slice(B256::from(U256::from(27)).as_slice());
+        input.extend_from_slice(&signature[..64]);
+        vm.mock_static_call(
+            ECRECOVER,
+            input,
+            Ok(B256::left_padding_from(server.as_slice()).to_vec()),
+        );
+
+        vm.set_sender(player);
+        assert!(matches!(
+            contract.increment_sword(blue),
+            Err(CounterError::UnauthorizedAccount(_))
+        ));
+        assert!(matches!(
+            contract.set_drop_signer(player),
+            Err(CounterError::UnauthorizedAccount(_))
+        ));
+
+        assert!(contract
+            .claim_sword(blue, U256::ZERO, expiry, Bytes(signature.clone()))
+            .is_ok());
+        assert_eq!(U256::from(1), contract.get_sword_count(blue));
+        assert_eq!(U256::from(1), contract.drop_nonce(player));
+
+        // The same claim can't be replayed.
+        assert!(matches!(
+            contract.claim_sword(blue, U256::ZERO, expiry, Bytes(signature.clone())),
+            Err(CounterError::InvalidDropNonce(_))
+        ));
+
+        // Nor can it be redirected to another color, since the signature no longer matches.
+        assert!(matches!(
+            contract.claim_sword(
+                U256::from(2),
+                U256::from(1),
+                expiry,
+                Bytes(signature.clone())
+            ),
+            Err(CounterError::InvalidDropSignature(_))
+        ));
+
+        vm.set_block_timestamp(2_000);
+        assert!(matches!(
+            contract.claim_sword(blue, U256::from(1), expiry, Bytes(signature)),
+            Err(CounterError::DropExpired(_))
+        ));
+        assert_eq!(U256::from(1), contract.get_sword_count(blue));
+    }
+
+    #[test]
+    fn test_drop_claim_hash_matches_eth_sign() {
+        use ethers::{abi::Token, types, utils};
+        let vm = TestVM::default();
+        let contract = deploy(&vm, &[]);
+
+        let address = Address::from([7u8; 20]);
+        let player = Address::from([1u8; 20]);
+        vm.set_contract_address(address);
+        vm.set_chain_id(412346);
+
+        // Hash the claim the way an ethers-based game server signs it.
+        let claim = ethers::abi::encode(&[
+            Token::Address(types::Address::from(address.0 .0)),
+            Token::Uint(types::U256::from(412346)),
+            Token::Address(types::Address::from(player.0 .0)),
+            Token::Uint(types::U256::from(2)),
+            Token::Uint(types::U256::from(5)),
+            Token::Uint(types::U256::from(1_000)),
+        ]);
+        let expected = utils::hash_message(utils::keccak256(claim));
+
+        let hash =
+            contract.drop_claim_hash(player, U256::from(2), U256::from(5), U256::from(1_000));
+        assert_eq!(expected.0, hash.0);
+    }
+
+    #[test]
+    fn test_signed_batch_drops() {
+        use crate::drops::ECRECOVER;
+        let vm = TestVM::default();
+        let mut contract = deploy(&vm, &[]);
+
+        let server = Address::from([9u8; 20]);
+        let player = Address::from([1u8; 20]);
+        let colors = vec![U256::from(0), U256::from(2)];
+        let amounts = vec![U256::from(3), U256::from(1)];
+        let expiry = U256::from(1_000);
+        vm.set_block_timestamp(500);
+        assert!(contract.set_drop_signer(server).is_ok());
+
+        // Fake signature; the ecrecover precompile is mocked to attribute it to `server`.
+        let mut signature = vec![0x11; 32];
+        signature.extend_from_slice(&[0x22; 32]);
+        signature.push(27);
+        let hash = contract.drop_batch_hash(player, &colors, &amounts, U256::ZERO, expiry);
+        let mut input = hash.to_vec();
+        input.extend_from_slice(B256::from(U256::from(27)).as_slice());
+        input.extend_from_slice(&signature[..64]);
+        vm.mock_static_call(
+            ECRECOVER,
+            input,
+            Ok(B256::left_padding_from(server.as_slice()).to_vec()),
+        );
+
+        vm.set_sender(player);
+        assert!(matches!(
+            contract.increment_swords(colors.clone(), amounts.clone()),
+            Err(CounterError::UnauthorizedAccount(_))
+        ));
+        assert!(contract
+            .claim_swords(
+                colors.clone(),
+                amounts.clone(),
+                U256::ZERO,
+                expiry,
+                Bytes(signature.clone())
+            )
+            .is_ok());
+        assert_eq!(
+            (U256::from(3), U256::ZERO, U256::from(1)),
+            contract.get_sword_counts()
+        );
+        assert_eq!(U256::from(1), contract.drop_nonce(player));
+
+        // The same claim can't be replayed.
+        assert!(matches!(
+            contract.claim_swords(
+                colors.clone(),
+                amounts.clone(),
+                U256::ZERO,
+                expiry,
+                Bytes(signature.clone())
+            ),
+            Err(CounterError::InvalidDropNonce(_))
+        ));
+
+        // Nor can the amounts be raised, since the signature no longer matches.
+        assert!(matches!(
+            contract.claim_swords(
+                colors.clone(),
+                vec![U256::from(30), U256::from(1)],
+                U256::from(1),
+                expiry,
+                Bytes(signature.clone())
+            ),
+            Err(CounterError::InvalidDropSignature(_))
+        ));
+        assert_eq!(U256::from(1), contract.drop_nonce(player));
+
+        vm.set_block_timestamp(2_000);
+        assert!(matches!(
+            contract.claim_swords(colors, amounts, U256::from(1), expiry, Bytes(signature)),
+            Err(CounterError::DropExpired(_))
+        ));
+        assert_eq!(U256::from(3), contract.get_sword_count(U256::from(0)));
+    }
+
+    #[test]
+    fn test_drop_batch_hash_matches_eth_sign() {
+        use ethers::{abi::Token, types, utils};
+        let vm = TestVM::default();
+        let contract = deploy(&vm, &[]);
+
+        let address = Address::from([7u8; 20]);
+        let player = Address::from([1u8; 20]);
+        vm.set_contract_address(address);
+        vm.set_chain_id(412346);
+
+        // Hash the claim the way the ethers-based drop signing service signs it.
+        let uints = |values: &[u64]| {
+            Token::Array(
+                values
+                    .iter()
+                    .map(|&value| Token::Uint(types::U256::from(value)))
+                    .collect(),
+            )
+        };
+        let claim = ethers::abi::encode(&[
+            Token::Address(types::Address::from(address.0 .0)),
+            Token::Uint(types::U256::from(412346)),
+            Token::Address(types::Address::from(player.0 .0)),
+            uints(&[0, 2]),
+            uints(&[3, 1]),
+            Token::Uint(types::U256::from(5)),
+            Token::Uint(types::U256::from(1_000)),
+        ]);
+        let expected = utils::hash_message(utils::keccak256(claim));
+
+        let hash = contract.drop_batch_hash(
+            player,
+            &[U256::from(0), U256::from(2)],
+            &[U256::from(3), U256::from(1)],
+            U256::from(5),
+            U256::from(1_000),
+        );
+        assert_eq!(expected.0, hash.0);
+    }
+
+    #[test]
+    fn test_roles() {
+        let vm = TestVM::default();
+        let mut contract = deploy(&vm, &[]);
+        let minter = Address::from([1u8; 20]);
+        let admin_role = B256::from(DEFAULT_ADMIN_ROLE);
+        let minter_role = B256::from(MINTER_ROLE);
+
+        assert_eq!(OWNER, contract.owner());
+        assert!(contract.has_role(admin_role, OWNER));
+        assert!(contract.has_role(minter_role, OWNER));
+        assert_eq!(admin_role, contract.get_role_admin(minter_role));
+
+        assert!(contract.grant_role(minter_role, minter).is_ok());
+        assert!(contract.has_role(minter_role, minter));
+
+        vm.set_sender(minter);
+        assert!(contract.increment_sword(U256::from(1)).is_ok());
+        assert!(matches!(
+            contract.grant_role(minter_role, Address::from([2u8; 20])),
+            Err(AccessError::UnauthorizedAccount(_))
+        ));
+        assert!(matches!(
+            contract.renounce_role(minter_role, OWNER),
+            Err(AccessError::BadConfirmation(_))
+        ));
+        assert!(contract.renounce_role(minter_role, minter).is_ok());
+        assert!(!contract.has_role(minter_role, minter));
+
+        vm.set_sender(OWNER);
+        assert!(contract.grant_role(minter_role, minter).is_ok());
+        assert!(contract.revoke_role(minter_role, minter).is_ok());
+        assert!(!contract.has_role(minter_role, minter));
+    }
+
+    #[test]
+    fn test_two_step_ownership_transfer() {
+        let vm = TestVM::default();
+        let mut contract = deploy(&vm, &[]);
+        let new_owner = Address::from([1u8; 20]);
+        let admin_role = B256::from(DEFAULT_ADMIN_ROLE);
+
+        vm.set_sender(new_owner);
+        assert!(matches!(
+            contract.transfer_ownership(new_owner),
+            Err(AccessError::OwnableUnauthorizedAccount(_))
+        ));
+
+        vm.set_sender(OWNER);
+        assert!(contract.transfer_ownership(new_owner).is_ok());
+        assert_eq!(new_owner, contract.pending_owner());
+        assert_eq!(OWNER, contract.owner());
+
+        // Only the pending owner can accept.
+        assert!(matches!(
+            contract.accept_ownership(),
+            Err(AccessError::OwnableUnauthorizedAccount(_))
+        ));
+
+        vm.set_sender(new_owner);
+        assert!(contract.accept_ownership().is_ok());
+        assert_eq!(new_owner, contract.owner());
+        assert_eq!(Address::ZERO, contract.pending_owner());
+        assert!(contract.has_role(admin_role, new_owner));
+        assert!(!contract.has_role(admin_role, OWNER));
+    }
+
+    #[test]
+    fn test_number_events() {
+        use alloy_sol_types::SolEvent;
+        let vm = TestVM::default();
+        let mut contract = deploy(&vm, &[]);
+
+        assert!(contract.set_number(U256::from(41)).is_ok());
+        assert!(contract.increment().is_ok());
+
+        let logs = vm.get_emitted_logs();
+        let changes: Vec<NumberChanged> = logs
+            .iter()
+            .filter(|(topics, _)| topics[0] == NumberChanged::SIGNATURE_HASH)
+            .map(|(topics, data)| {
+                NumberChanged::decode_raw_log(topics.clone(), data, true).unwrap()
+            })
+            .collect();
+        assert_eq!(2, changes.len());
+        assert_eq!(U256::ZERO, changes[0].oldNumber);
+        assert_eq!(U256::from(41), changes[0].newNumber);
+        assert_eq!(U256::from(41), changes[1].oldNumber);
+        assert_eq!(U256::from(42), changes[1].newNumber);
+    }
+
+    #[test]
+    fn test_sword_collected_events() {
+        use crate::erc1155::TransferSingle;
+        use alloy_sol_types::SolEvent;
+        let vm = TestVM::default();
+        let player = Address::from([1u8; 20]);
+        let mut contract = deploy(&vm, &[player]);
+        let green = U256::from(2);
+
+        vm.set_sender(player);
+        assert!(contract.increment_sword(green).is_ok());
+        assert!(contract.increment_sword(green).is_ok());
+
+        let logs = vm.get_emitted_logs();
+        let (topics, data) = logs.last().unwrap();
+        assert_eq!(SwordCollected::SIGNATURE_HASH, topics[0]);
+        assert_eq!(B256::left_padding_from(player.as_slice()), topics[1]);
+        assert_eq!(B256::from(green), topics[2]);
+        let collected = SwordCollected::decode_raw_log(topics.clone(), data, true).unwrap();
+        assert_eq!(player, collected.player);
+        assert_eq!(green, collected.color);
+        assert_eq!(U256::from(2), collected.newCount);
+
+        // Each pickup is also an ERC-1155 mint.
+        let (topics, data) = &logs[logs.len() - 2];
+        let minted = TransferSingle::decode_raw_log(topics.clone(), data, true).unwrap();
+        assert_eq!(Address::ZERO, minted.from);
+        assert_eq!(player, minted.to);
+        assert_eq!(green, minted.id);
+        assert_eq!(U256::from(1), minted.value);
+    }
+
+    #[test]
+    fn test_drop_signer_changed_event() {
+        use alloy_sol_types::SolEvent;
+        let vm = TestVM::default();
+        let mut contract = deploy(&vm, &[]);
+        let server = Address::from([9u8; 20]);
+
+        assert!(contract.set_drop_signer(server).is_ok());
+
+        let logs = vm.get_emitted_logs();
+        let (topics, data) = logs.last().unwrap();
+        let changed = DropSignerChanged::decode_raw_log(topics.clone(), data, true).unwrap();
+        assert_eq!(Address::ZERO, changed.previousSigner);
+        assert_eq!(server, changed.newSigner);
+    }
+
+    #[test]
+    fn test_custom_errors() {
+        use alloy_sol_types::SolError;
+        let vm = TestVM::default();
+        let player = Address::from([1u8; 20]);
+        let mut contract = deploy(&vm, &[player]);
+
+        vm.set_sender(player);
+        let err = contract.increment_sword(U256::from(3)).err().unwrap();
+        assert_eq!(
+            InvalidColor {
+                color: U256::from(3)
+            }
+            .abi_encode(),
+            Vec::<u8>::from(err)
+        );
+
+        let err = contract.set_number(U256::from(1)).err().unwrap();
+        assert_eq!(
+            AccessControlUnauthorizedAccount {
+                account: player,
+                neededRole: DEFAULT_ADMIN_ROLE.into(),
+            }
+            .abi_encode(),
+            Vec::<u8>::from(err)
+        );
+
+        vm.set_sender(OWNER);
+        assert!(contract.set_number(U256::MAX).is_ok());
+        let err = contract.increment().err().unwrap();
+        assert_eq!(Overflow {}.abi_encode(), Vec::<u8>::from(err));
+        assert_eq!(U256::MAX, contract.number());
+    }
+
+    #[test]
+    fn test_color_registry() {
+        let vm = TestVM::default();
+        let player = Address::from([1u8; 20]);
+        let mut contract = deploy(&vm, &[player]);
+
+        assert_eq!(U256::from(3), contract.color_count());
+        let (names, weights, enabled) = contract.get_colors();
+        assert_eq!(vec!["red", "blue", "green"], names);
+        assert_eq!(vec![U256::from(1); 3], weights);
+        assert_eq!(vec![true; 3], enabled);
+
+        let gold = contract
+            .add_color("gold".into(), U256::from(5), true)
+            .ok()
+            .unwrap();
+        assert_eq!(U256::from(3), gold);
+        assert!(matches!(
+            contract.get_color(gold),
+            Ok((name, weight, true)) if name == "gold" && weight == U256::from(5)
+        ));
+        assert!(matches!(
+            contract.get_color(U256::from(4)),
+            Err(CounterError::InvalidColor(_))
+        ));
+
+        vm.set_sender(player);
+        assert!(contract.increment_sword(gold).is_ok());
+        assert!(matches!(
+            contract.add_color("silver".into(), U256::from(1), true),
+            Err(CounterError::UnauthorizedAccount(_))
+        ));
+        assert!(matches!(
+            contract.set_color(gold, "gold".into(), U256::from(5), false),
+            Err(CounterError::UnauthorizedAccount(_))
+        ));
+
+        vm.set_sender(OWNER);
+        assert!(contract
+            .set_color(gold, "golden".into(), U256::from(2), false)
+            .is_ok());
+        assert!(matches!(
+            contract.get_color(gold),
+            Ok((name, weight, false)) if name == "golden" && weight == U256::from(2)
+        ));
+        assert!(matches!(
+            contract.set_color(U256::from(9), "nope".into(), U256::ZERO, true),
+            Err(CounterError::InvalidColor(_))
+        ));
+
+        // Disabled colors can no longer be minted, but balances stay put.
+        vm.set_sender(player);
+        assert!(matches!(
+            contract.increment_sword(gold),
+            Err(CounterError::InvalidColor(_))
+        ));
+        assert_eq!(U256::from(1), contract.get_sword_count_of(player, gold));
+    }
+
+    #[test]
+    fn test_crafting() {
+        use crate::crafting::Crafted;
+        use alloy_sol_types::SolEvent;
+        let vm = TestVM::default();
+        let player = Address::from([1u8; 20]);
+        let mut contract = deploy(&vm, &[player]);
+        let (red, blue) = (U256::from(0), U256::from(1));
+
+        // Crimson never drops; it can only be crafted.
+        let crimson = contract
+            .add_color("crimson".into(), U256::ZERO, false)
+            .ok()
+            .unwrap();
+        let recipe = contract
+            .add_recipe(
+                vec![red, blue],
+                vec![U256::from(3), U256::from(1)],
+                crimson,
+                U256::from(1),
+            )
+            .ok()
+            .unwrap();
+        assert_eq!(U256::ZERO, recipe);
+        assert_eq!(U256::from(1), contract.recipe_count());
+        assert!(matches!(
+            contract.get_recipe(recipe),
+            Ok((inputs, amounts, output, amount, true))
+                if inputs == vec![red, blue]
+                    && amounts == vec![U256::from(3), U256::from(1)]
+                    && output == crimson
+                    && amount == U256::from(1)
+        ));
+        assert!(matches!(
+            contract.add_recipe(vec![red], vec![], crimson, U256::from(1)),
+            Err(CounterError::InvalidRecipe(_))
+        ));
+        assert!(matches!(
+            contract.add_recipe(
+                vec![red, red],
+                vec![U256::from(1), U256::from(1)],
+                crimson,
+                U256::from(1)
+            ),
+            Err(CounterError::InvalidRecipe(_))
+        ));
+        assert!(matches!(
+            contract.add_recipe(vec![red], vec![U256::from(1)], U256::from(9), U256::from(1)),
+            Err(CounterError::InvalidColor(_))
+        ));
+
+        vm.set_sender(player);
+        for _ in 0..3 {
+            assert!(contract.increment_sword(red).is_ok());
+        }
+        assert!(matches!(
+            contract.craft(recipe),
+            Err(CounterError::InsufficientSwords(_))
+        ));
+        assert_eq!(U256::from(3), contract.get_sword_count(red));
+        assert!(contract.increment_sword(blue).is_ok());
+        assert!(contract.increment_sword(blue).is_ok());
+        assert!(contract.craft(recipe).is_ok());
+        assert_eq!(U256::ZERO, contract.get_sword_count(red));
+        assert_eq!(U256::from(1), contract.get_sword_count(blue));
+        assert_eq!(U256::from(1), contract.get_sword_count(crimson));
+
+        let logs = vm.get_emitted_logs();
+        let (topics, data) = logs.last().unwrap();
+        let crafted = Crafted::decode_raw_log(topics.clone(), data, true).unwrap();
+        assert_eq!(player, crafted.player);
+        assert_eq!(recipe, crafted.recipeId);
+        assert_eq!(crimson, crafted.outputColor);
+
+        assert!(matches!(
+            contract.set_recipe_enabled(recipe, false),
+            Err(CounterError::UnauthorizedAccount(_))
+        ));
+        vm.set_sender(OWNER);
+        assert!(contract.set_recipe_enabled(recipe, false).is_ok());
+        vm.set_sender(player);
+        assert!(matches!(
+            contract.craft(recipe),
+            Err(CounterError::InvalidRecipe(_))
+        ));
+        assert!(matches!(
+            contract.craft(U256::from(7)),
+            Err(CounterError::InvalidRecipe(_))
+        ));
+    }
+
+    #[test]
+    fn test_undroppable_colors() {
+        use crate::drops::ECRECOVER;
+        let vm = TestVM::default();
+        let minter = Address::from([1u8; 20]);
+        let player = Address::from([2u8; 20]);
+        let server = Address::from([9u8; 20]);
+        let mut contract = deploy(&vm, &[minter]);
+        let red = U256::from(0);
+        let expiry = U256::from(1_000);
+        vm.set_block_timestamp(500);
+        assert!(contract.set_drop_signer(server).is_ok());
+
+        let crimson = contract
+            .add_color("crimson".into(), U256::ZERO, false)
+            .ok()
+            .unwrap();
+        assert!(contract.is_color_droppable(red));
+        assert!(!contract.is_color_droppable(crimson));
+        assert!(!contract.is_color_droppable(U256::from(9)));
+
+        // Not even minters can mint it as a drop, and one such color rejects the whole batch.
+        vm.set_sender(minter);
+        assert!(matches!(
+            contract.increment_sword(crimson),
+            Err(CounterError::ColorNotDroppable(_))
+        ));
+        assert!(matches!(
+            contract.increment_swords(vec![red, crimson], vec![U256::from(1), U256::from(1)]),
+            Err(CounterError::ColorNotDroppable(_))
+        ));
+        assert_eq!(U256::ZERO, contract.get_sword_count(red));
+
+        // A claim for it is refused even with a valid signature.
+        let colors = vec![crimson];
+        let amounts = vec![U256::from(1)];
+        let mut signature = vec![0x11; 32];
+        signature.extend_from_slice(&[0x22; 32]);
+        signature.push(27);
+        let hash = contract.drop_batch_hash(player, &colors, &amounts, U256::ZERO, expiry);
+        let mut input = hash.to_vec();
+        input.extend_from_slice(B256::from(U256::from(27)).as_slice());
+        input.extend_from_slice(&signature[..64]);
+        vm.mock_static_call(
+            ECRECOVER,
+            input,
+            Ok(B256::left_padding_from(server.as_slice()).to_vec()),
+        );
+        vm.set_sender(player);
+        assert!(matches!(
+            contract.claim_swords(colors, amounts, U256::ZERO, expiry, Bytes(signature)),
+            Err(CounterError::ColorNotDroppable(_))
+        ));
+        assert_eq!(U256::ZERO, contract.get_sword_count(crimson));
+
+        assert!(matches!(
+            contract.set_color_droppable(crimson, true),
+            Err(CounterError::UnauthorizedAccount(_))
+        ));
+        vm.set_sender(OWNER);
+        assert!(matches!(
+            contract.set_color_droppable(U256::from(9), true),
+            Err(CounterError::InvalidColor(_))
+        ));
+        assert!(contract.set_color_droppable(crimson, true).is_ok());
+        assert!(contract.is_color_droppable(crimson));
+        vm.set_sender(minter);
+        assert!(contract.increment_sword(crimson).is_ok());
+        assert_eq!(U256::from(1), contract.get_sword_count(crimson));
+    }
+
+    #[test]
+    fn test_increment_swords_batch() {
+        use alloy_sol_types::SolEvent;
+        let vm = TestVM::default();
+        let player = Address::from([1u8; 20]);
+        let mut contract = deploy(&vm, &[player]);
+        let (red, green) = (U256::from(0), U256::from(2));
+
+        vm.set_sender(player);
+        assert!(contract
+            .increment_swords(vec![red, green], vec![U256::from(4), U256::from(2)])
+            .is_ok());
+        assert_eq!(
+            (U256::from(4), U256::ZERO, U256::from(2)),
+            contract.get_sword_counts()
+        );
+
+        let collected: Vec<SwordCollected> = vm
+            .get_emitted_logs()
+            .iter()
+            .filter(|(topics, _)| topics[0] == SwordCollected::SIGNATURE_HASH)
+            .map(|(topics, data)| {
+                SwordCollected::decode_raw_log(topics.clone(), data, true).unwrap()
+            })
+            .collect();
+        assert_eq!(2, collected.len());
+        assert_eq!(U256::from(4), collected[0].newCount);
+        assert_eq!(green, collected[1].color);
+
+        assert!(matches!(
+            contract.increment_swords(vec![red], vec![]),
+            Err(CounterError::InvalidArrayLength(_))
+        ));
+        // One bad color rejects the whole batch.
+        assert!(matches!(
+            contract.increment_swords(vec![red, U256::from(7)], vec![U256::from(1), U256::from(1)]),
+            Err(CounterError::InvalidColor(_))
+        ));
+        assert_eq!(U256::from(4), contract.get_sword_count(red));
+
+        vm.set_sender(Address::from([2u8; 20]));
+        assert!(matches!(
+            contract.increment_swords(vec![red], vec![U256::from(1)]),
+            Err(CounterError::UnauthorizedAccount(_))
+        ));
     }
 }
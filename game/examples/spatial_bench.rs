@@ -0,0 +1,106 @@
+// Headless benchmark of the spatial hash against scanning every enemy, the way collision and pickup
+// checks used to. Spawns a few thousand moving enemies in an app without a window and times frames
+// that look up the enemies around a number of points, with both approaches.
+//
+//     cargo run --release --example spatial_bench [enemies]
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::time::Instant;
+
+#[path = "../src/spatial.rs"]
+mod spatial;
+
+use spatial::{rebuild_spatial_hash, SpatialHash};
+
+const WORLD_SIZE: f32 = 3000.0;
+const QUERY_RANGE: f32 = 60.0;
+const CELL_SIZE: f32 = 128.0;
+const ENEMY_SPEED: f32 = 100.0;
+const WARMUP_FRAMES: u32 = 10;
+const FRAMES: u32 = 200;
+
+#[derive(Component)]
+struct Enemy;
+
+// Points to look up each frame, like the sword, the player and pickups
+#[derive(Resource)]
+struct Probes(Vec<Vec2>);
+
+// Enemies found over the whole run, to check both approaches agree
+#[derive(Resource, Default)]
+struct Found(u64);
+
+fn main() {
+    let enemies = std::env::args().nth(1).and_then(|count| count.parse().ok()).unwrap_or(4000);
+    println!("{} enemies, {} frames", enemies, FRAMES);
+    println!("{:>8} {:>14} {:>14} {:>8}", "queries", "linear ms", "grid ms", "speedup");
+    for queries in [1, 3, 10, 100] {
+        let (linear, linear_found) = run(enemies, queries, false);
+        let (grid, grid_found) = run(enemies, queries, true);
+        assert_eq!(linear_found, grid_found, "both approaches should find the same enemies");
+        println!("{:>8} {:>14.3} {:>14.3} {:>7.1}x", queries, linear, grid, linear / grid);
+    }
+}
+
+// Milliseconds per frame, and how many enemies were found
+fn run(enemies: usize, queries: usize, grid: bool) -> (f64, u64) {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut random_point = move || Vec2::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5)) * WORLD_SIZE;
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(Probes((0..queries).map(|_| random_point()).collect()))
+        .init_resource::<Found>()
+        .add_systems(Update, move_enemies);
+    if grid {
+        app.insert_resource(SpatialHash::<Enemy>::new(CELL_SIZE, 0.0))
+            .add_systems(Update, (rebuild_spatial_hash::<Enemy>, find_with_grid).chain().after(move_enemies));
+    } else {
+        app.add_systems(Update, find_linear.after(move_enemies));
+    }
+    for _ in 0..enemies {
+        app.world_mut().spawn((Enemy, Transform::from_translation(random_point().extend(0.0))));
+    }
+
+    for _ in 0..WARMUP_FRAMES {
+        app.update();
+    }
+    app.world_mut().resource_mut::<Found>().0 = 0;
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        app.update();
+    }
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0 / FRAMES as f64;
+    (elapsed, app.world().resource::<Found>().0)
+}
+
+// A fixed step, so both runs move the enemies the same way
+fn move_enemies(mut query: Query<&mut Transform, With<Enemy>>) {
+    for mut transform in query.iter_mut() {
+        let direction = -transform.translation.truncate().normalize_or_zero();
+        transform.translation += (direction * ENEMY_SPEED / 60.0).extend(0.0);
+    }
+}
+
+fn find_linear(probes: Res<Probes>, query: Query<&Transform, With<Enemy>>, mut found: ResMut<Found>) {
+    for probe in &probes.0 {
+        found.0 += query.iter()
+            .filter(|transform| transform.translation.truncate().distance(*probe) < QUERY_RANGE)
+            .count() as u64;
+    }
+}
+
+fn find_with_grid(
+    probes: Res<Probes>,
+    hash: Res<SpatialHash<Enemy>>,
+    query: Query<&Transform, With<Enemy>>,
+    mut found: ResMut<Found>,
+) {
+    for probe in &probes.0 {
+        found.0 += hash.query_circle(*probe, QUERY_RANGE)
+            .filter_map(|candidate| query.get(candidate).ok())
+            .filter(|transform| transform.translation.truncate().distance(*probe) < QUERY_RANGE)
+            .count() as u64;
+    }
+}
@@ -0,0 +1,389 @@
+// Sprite sheets and the clips played from them, read from `assets/animations.json`. A sheet is a grid
+// of frames in one image, loaded as a `TextureAtlasLayout`. Sheets with `{variant}` in their image
+// path are loaded once per variant (a sword color, an enemy sprite), all sharing the same layout and
+// clips. An `Animator` plays one clip at a time on a sprite, with the clip's frame timings and loop mode.
+
+use bevy::prelude::*;
+use eyre::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub const ANIMATIONS_PATH: &str = "assets/animations.json";
+const VARIANT: &str = "{variant}";
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopMode {
+    #[default]
+    Loop,
+    // Stops on the last frame
+    Once,
+    // Plays forwards, then backwards, and again
+    PingPong,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Clip {
+    // Cells of the sheet, counted left to right and top to bottom
+    pub frames: Vec<usize>,
+    #[serde(default = "default_fps")]
+    pub fps: f32,
+    // Seconds each frame lasts, instead of `fps`
+    #[serde(default)]
+    pub durations: Vec<f32>,
+    #[serde(default)]
+    pub mode: LoopMode,
+}
+
+fn default_fps() -> f32 {
+    8.0
+}
+
+impl Clip {
+    fn new(frames: Vec<usize>, fps: f32, mode: LoopMode) -> Self {
+        Clip { frames, fps, durations: Vec::new(), mode }
+    }
+
+    fn duration(&self, frame: usize) -> f32 {
+        self.durations.get(frame).copied().unwrap_or(1.0 / self.fps)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SheetDef {
+    // Relative to `assets/`, with `{variant}` replaced by the variant's name
+    image: String,
+    tile_size: [u32; 2],
+    columns: u32,
+    rows: u32,
+    #[serde(default)]
+    clips: HashMap<String, Clip>,
+}
+
+#[derive(Resource, Deserialize)]
+pub struct AnimationTable {
+    sheets: HashMap<String, SheetDef>,
+}
+
+impl Default for AnimationTable {
+    // The sheets in `assets/sheets`: a row per direction for the player and the swords, and a single
+    // frame for items
+    fn default() -> Self {
+        let mut player = HashMap::new();
+        let mut sword = HashMap::new();
+        for (row, direction) in ["up", "down", "left", "right"].iter().enumerate() {
+            let first = row * 4;
+            player.insert(format!("idle_{}", direction), Clip::new(vec![first], 8.0, LoopMode::Loop));
+            player.insert(format!("walk_{}", direction), Clip::new(vec![first, first + 1], 8.0, LoopMode::Loop));
+            sword.insert(format!("swing_{}", direction), Clip::new((first..first + 4).collect(), 12.0, LoopMode::Once));
+        }
+        let enemy = HashMap::from([("walk".to_string(), Clip::new(vec![0, 1], 6.0, LoopMode::Loop))]);
+        let sheet = |image: &str, columns, rows, clips| SheetDef { image: image.to_string(), tile_size: [32, 32], columns, rows, clips };
+        AnimationTable {
+            sheets: HashMap::from([
+                ("player".to_string(), sheet("sheets/player.png", 4, 4, player)),
+                ("sword".to_string(), sheet("sheets/swords/{variant}.png", 4, 4, sword)),
+                ("enemy".to_string(), sheet("sheets/enemy/{variant}.png", 2, 1, enemy)),
+                ("item".to_string(), sheet("sheets/items/{variant}.png", 1, 1, HashMap::new())),
+            ]),
+        }
+    }
+}
+
+impl AnimationTable {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let table: AnimationTable = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        for (sheet_name, sheet) in &table.sheets {
+            let cells = (sheet.columns * sheet.rows) as usize;
+            for (clip_name, clip) in &sheet.clips {
+                if clip.frames.is_empty() {
+                    eyre::bail!("clip {}/{} has no frames", sheet_name, clip_name);
+                }
+                if let Some(frame) = clip.frames.iter().find(|&&frame| frame >= cells) {
+                    eyre::bail!("clip {}/{} uses frame {}, but the sheet has {}", sheet_name, clip_name, frame, cells);
+                }
+                if clip.durations.is_empty() && clip.fps <= 0.0 {
+                    eyre::bail!("clip {}/{} needs a positive fps", sheet_name, clip_name);
+                }
+                if !clip.durations.is_empty()
+                    && (clip.durations.len() != clip.frames.len() || clip.durations.iter().any(|&secs| secs <= 0.0)) {
+                    eyre::bail!("clip {}/{} needs a positive duration for each of its frames", sheet_name, clip_name);
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    pub fn clip(&self, sheet: &str, clip: &str) -> Option<&Clip> {
+        self.sheets.get(sheet)?.clips.get(clip)
+    }
+}
+
+pub fn load_animation_table(mut commands: Commands) {
+    let table = AnimationTable::load(ANIMATIONS_PATH).unwrap_or_else(|e| {
+        eprintln!("Couldn't load {}, using the default animations: {}", ANIMATIONS_PATH, e);
+        AnimationTable::default()
+    });
+    commands.insert_resource(table);
+}
+
+struct LoadedSheet {
+    layout: Handle<TextureAtlasLayout>,
+    // Image per variant name, "" for sheets without variants
+    variants: HashMap<String, Handle<Image>>,
+}
+
+// The sheets loaded so far
+#[derive(Resource, Default)]
+pub struct SpriteSheets {
+    sheets: HashMap<String, LoadedSheet>,
+}
+
+impl SpriteSheets {
+    // Starts loading `variant` of `sheet`, unless it already is
+    pub fn load(
+        &mut self,
+        sheet: &str,
+        variant: &str,
+        table: &AnimationTable,
+        asset_server: &AssetServer,
+        layouts: &mut Assets<TextureAtlasLayout>,
+    ) {
+        let Some(def) = table.sheets.get(sheet) else {
+            eprintln!("{} has no sheet named {}", ANIMATIONS_PATH, sheet);
+            return;
+        };
+        let loaded = self.sheets.entry(sheet.to_string()).or_insert_with(|| LoadedSheet {
+            layout: layouts.add(TextureAtlasLayout::from_grid(UVec2::from(def.tile_size), def.columns, def.rows, None, None)),
+            variants: HashMap::new(),
+        });
+        if !loaded.variants.contains_key(variant) {
+            loaded.variants.insert(variant.to_string(), asset_server.load(def.image.replace(VARIANT, variant)));
+        }
+    }
+
+    pub fn get(&self, sheet: &str, variant: &str) -> Option<(&Handle<Image>, &Handle<TextureAtlasLayout>)> {
+        let loaded = self.sheets.get(sheet)?;
+        Some((loaded.variants.get(variant)?, &loaded.layout))
+    }
+}
+
+// Plays a clip on a sprite that has a `TextureAtlas`
+#[derive(Component)]
+pub struct Animator {
+    sheet: String,
+    variant: String,
+    clip: String,
+    // Position in the clip's frames, not the sheet's
+    frame: usize,
+    timer: f32,
+    // Whether a ping-pong clip is on its way back
+    reversed: bool,
+    finished: bool,
+    // Playback rate, 1 being the clip's own timing
+    pub speed: f32,
+}
+
+impl Animator {
+    pub fn new(sheet: &str, variant: &str, clip: &str) -> Self {
+        Animator {
+            sheet: sheet.to_string(),
+            variant: variant.to_string(),
+            clip: clip.to_string(),
+            frame: 0,
+            timer: 0.0,
+            reversed: false,
+            finished: false,
+            speed: 1.0,
+        }
+    }
+
+    // Switches to `clip` from its start, unless it's the one playing
+    pub fn play(&mut self, sheet: &str, variant: &str, clip: &str) {
+        if self.sheet != sheet || self.variant != variant || self.clip != clip {
+            *self = Animator::new(sheet, variant, clip);
+        }
+    }
+
+    // Plays the current clip again from its start
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.timer = 0.0;
+        self.reversed = false;
+        self.finished = false;
+    }
+
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    // Whether a `once` clip is done with its last frame
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn advance(&mut self, clip: &Clip, delta: f32) {
+        let last = clip.frames.len() - 1;
+        self.frame = self.frame.min(last);
+        self.timer += delta * self.speed;
+        while !self.finished && self.timer >= clip.duration(self.frame) {
+            self.timer -= clip.duration(self.frame);
+            match clip.mode {
+                LoopMode::Loop => self.frame = if self.frame == last { 0 } else { self.frame + 1 },
+                LoopMode::Once if self.frame == last => self.finished = true,
+                LoopMode::Once => self.frame += 1,
+                LoopMode::PingPong if last == 0 => {}
+                LoopMode::PingPong => {
+                    if self.frame == 0 {
+                        self.reversed = false;
+                    } else if self.frame == last {
+                        self.reversed = true;
+                    }
+                    self.frame = if self.reversed { self.frame - 1 } else { self.frame + 1 };
+                }
+            }
+        }
+    }
+}
+
+// Advances every animator and shows its frame
+pub fn animate(
+    mut query: Query<(&mut Animator, &mut Handle<Image>, &mut TextureAtlas)>,
+    table: Res<AnimationTable>,
+    sheets: Res<SpriteSheets>,
+    time: Res<Time>,
+) {
+    for (mut animator, mut texture, mut atlas) in query.iter_mut() {
+        let Some(clip) = table.clip(&animator.sheet, &animator.clip) else {
+            continue;
+        };
+        animator.advance(clip, time.delta_seconds());
+
+        // Clips can come from another sheet than the last one, like the player's swings
+        let Some((image, layout)) = sheets.get(&animator.sheet, &animator.variant) else {
+            continue;
+        };
+        if *texture != *image {
+            *texture = image.clone();
+        }
+        if atlas.layout != *layout {
+            atlas.layout = layout.clone();
+        }
+        let index = clip.frames[animator.frame];
+        if atlas.index != index {
+            atlas.index = index;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The clip positions an animator shows over `steps` ticks of one frame's duration each
+    fn play(clip: &Clip, steps: usize) -> Vec<usize> {
+        let mut animator = Animator::new("sheet", "", "clip");
+        (0..steps)
+            .map(|_| {
+                animator.advance(clip, 1.0 / clip.fps);
+                animator.frame()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loops_wrap_to_the_first_frame() {
+        let clip = Clip::new(vec![4, 5, 6], 10.0, LoopMode::Loop);
+        assert_eq!(play(&clip, 7), vec![1, 2, 0, 1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        let clip = Clip::new(vec![0, 1, 2, 3], 12.0, LoopMode::Once);
+        let mut animator = Animator::new("sword", "blue", "swing_up");
+        for expected in 1..=3 {
+            animator.advance(&clip, 1.0 / 12.0);
+            assert_eq!(animator.frame(), expected);
+            assert!(!animator.is_finished());
+        }
+        // The last frame still gets its full duration before the clip counts as finished
+        animator.advance(&clip, 0.5 / 12.0);
+        assert!(!animator.is_finished());
+        animator.advance(&clip, 0.5 / 12.0);
+        assert!(animator.is_finished());
+        animator.advance(&clip, 10.0);
+        assert_eq!(animator.frame(), 3);
+
+        animator.restart();
+        assert_eq!(animator.frame(), 0);
+        assert!(!animator.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_turns_around_at_both_ends() {
+        let clip = Clip::new(vec![0, 1, 2], 10.0, LoopMode::PingPong);
+        assert_eq!(play(&clip, 8), vec![1, 2, 1, 0, 1, 2, 1, 0]);
+        // A single frame has nowhere to go
+        assert_eq!(play(&Clip::new(vec![7], 10.0, LoopMode::PingPong), 3), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn long_ticks_skip_frames() {
+        let clip = Clip::new(vec![0, 1, 2, 3], 10.0, LoopMode::Loop);
+        let mut animator = Animator::new("sheet", "", "clip");
+        animator.advance(&clip, 0.55);
+        assert_eq!(animator.frame(), 1);
+
+        let once = Clip::new(vec![0, 1, 2], 10.0, LoopMode::Once);
+        let mut animator = Animator::new("sheet", "", "clip");
+        animator.advance(&once, 5.0);
+        assert_eq!(animator.frame(), 2);
+        assert!(animator.is_finished());
+    }
+
+    #[test]
+    fn durations_and_speed_set_the_timing() {
+        let clip = Clip { durations: vec![0.1, 0.5], ..Clip::new(vec![0, 1], 1.0, LoopMode::Loop) };
+        let mut animator = Animator::new("sheet", "", "clip");
+        animator.advance(&clip, 0.1);
+        assert_eq!(animator.frame(), 1);
+        animator.advance(&clip, 0.4);
+        assert_eq!(animator.frame(), 1);
+        animator.advance(&clip, 0.1);
+        assert_eq!(animator.frame(), 0);
+
+        animator.speed = 2.0;
+        animator.advance(&clip, 0.05);
+        assert_eq!(animator.frame(), 1);
+    }
+
+    #[test]
+    fn play_only_restarts_for_another_clip() {
+        let clip = Clip::new(vec![0, 1, 2], 10.0, LoopMode::Loop);
+        let mut animator = Animator::new("player", "", "walk_up");
+        animator.advance(&clip, 0.1);
+        animator.play("player", "", "walk_up");
+        assert_eq!(animator.frame(), 1);
+        animator.play("player", "", "walk_down");
+        assert_eq!(animator.frame(), 0);
+    }
+
+    #[test]
+    fn shipped_animations_load() {
+        let table = AnimationTable::load(ANIMATIONS_PATH).unwrap();
+        for direction in ["up", "down", "left", "right"] {
+            assert!(matches!(table.clip("sword", &format!("swing_{}", direction)), Some(Clip { mode: LoopMode::Once, .. })));
+            assert!(table.clip("player", &format!("walk_{}", direction)).is_some());
+        }
+    }
+
+    #[test]
+    fn frames_past_the_sheet_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("animations.json");
+        std::fs::write(&path, r#"{ "sheets": { "enemy": { "image": "enemy.png", "tile_size": [32, 32], "columns": 2, "rows": 1,
+            "clips": { "walk": { "frames": [0, 2] } } } } }"#).unwrap();
+        assert!(AnimationTable::load(&path).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+// Drop signing service: signs the batch drop claims the game submits with `claimSwords`. It holds
+// the key of the contract's drop signer, so players never get it. Each claim is for the swords
+// one flush of the game collected, capped per player with a rolling limit, and only for colors
+// the registry lets drop.
+//
+// It speaks plain HTTP on one route:
+//
+//     POST /claim {"player": "0x…", "colors": [0, 2], "amounts": [3, 1]}
+//       -> 200 {"nonce": "0x…", "expiry": 1700000000, "signature": "0x…"}
+//
+// Errors come back as `{"error": "…"}` with a 4xx or 5xx status.
+//
+// Settings come from the environment or `drop_signer.env`, never from the game's `.env`:
+// RPC_URL, STYLUS_CONTRACT_ADDRESS and DROP_SIGNER_PRIVATE_KEY, plus DROP_SIGNER_LISTEN (the address
+// to listen on) and DROP_SIGNER_MAX_SWORDS_PER_MINUTE. Run from `game/`:
+//
+//     cargo run --bin drop_signer
+
+use ethers::prelude::{abigen, Http, LocalWallet, Middleware, Provider};
+use ethers::signers::Signer;
+use ethers::{
+    abi::Token,
+    types::{Address, Bytes, U256},
+    utils::{hash_message, keccak256},
+};
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+abigen!(
+    DropContract,
+    r#"[
+        function dropSigner() external view returns (address)
+        function dropNonce(address player) external view returns (uint256)
+        function isColorDroppable(uint256 id) external view returns (bool)
+    ]"#
+);
+
+const ENV_FILE: &str = "drop_signer.env";
+const DEFAULT_LISTEN: &str = "127.0.0.1:8650";
+const DEFAULT_MAX_SWORDS_PER_MINUTE: u64 = 60;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+const CLAIM_TTL_SECS: u64 = 600; // Claims expire after 10 minutes
+const MAX_REQUEST_BYTES: usize = 16 * 1024;
+
+#[derive(Deserialize)]
+struct ClaimRequest {
+    player: Address,
+    colors: Vec<u8>,
+    amounts: Vec<u64>,
+}
+
+#[derive(Serialize)]
+struct ClaimResponse {
+    nonce: U256,
+    expiry: u64,
+    signature: Bytes,
+}
+
+struct Service {
+    signer: LocalWallet,
+    contract: DropContract<Provider<Http>>,
+    chain_id: u64,
+    max_swords_per_minute: u64,
+    // Swords signed for each player within the last minute, oldest first
+    recent: Mutex<HashMap<Address, VecDeque<(Instant, u64)>>>,
+}
+
+// A failed request, answered with its status
+struct Rejection(u16, String);
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv::from_filename(ENV_FILE).ok();
+    let var = |name: &str| std::env::var(name).map_err(|_| eyre!("{} isn't set", name));
+
+    let provider = Provider::<Http>::try_from(var("RPC_URL")?.as_str())?;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    let contract = DropContract::new(var("STYLUS_CONTRACT_ADDRESS")?.parse::<Address>()?, Arc::new(provider));
+    // The error could quote the key, so it's left out
+    let signer = LocalWallet::from_str(&var("DROP_SIGNER_PRIVATE_KEY")?)
+        .map_err(|_| eyre!("DROP_SIGNER_PRIVATE_KEY isn't a valid private key"))?;
+
+    let configured = contract.drop_signer().call().await?;
+    if configured != signer.address() {
+        eprintln!("The contract's drop signer is {:?}, not {:?}: its claims will be rejected until an admin calls setDropSigner", configured, signer.address());
+    }
+
+    let max_swords_per_minute = std::env::var("DROP_SIGNER_MAX_SWORDS_PER_MINUTE").ok()
+        .and_then(|max| max.parse().ok())
+        .unwrap_or(DEFAULT_MAX_SWORDS_PER_MINUTE);
+    let service = Arc::new(Service { signer, contract, chain_id, max_swords_per_minute, recent: Mutex::default() });
+
+    let listen = std::env::var("DROP_SIGNER_LISTEN").unwrap_or_else(|_| DEFAULT_LISTEN.to_string());
+    let listener = TcpListener::bind(&listen).await?;
+    println!("Signing drops as {:?} on {}", service.signer.address(), listen);
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let service = service.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve(&service, stream).await {
+                eprintln!("Request failed: {}", e);
+            }
+        });
+    }
+}
+
+// Answers the one request on `stream`
+async fn serve(service: &Service, mut stream: TcpStream) -> Result<()> {
+    let (status, body) = match read_request(&mut stream).await {
+        Ok((method, path, body)) if method == "POST" && path == "/claim" => match service.claim(&body).await {
+            Ok(response) => (200, serde_json::to_string(&response)?),
+            Err(Rejection(status, error)) => (status, serde_json::json!({ "error": error }).to_string()),
+        },
+        Ok(_) => (404, serde_json::json!({ "error": "only POST /claim is served" }).to_string()),
+        Err(e) => (400, serde_json::json!({ "error": e.to_string() }).to_string()),
+    };
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        _ => "Internal Server Error",
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason, body.len(), body,
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+// Reads the method, path and body of an HTTP/1.1 request, headers and body at most
+// `MAX_REQUEST_BYTES` in all
+async fn read_request(stream: &mut TcpStream) -> Result<(String, String, Vec<u8>)> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_BYTES as u64));
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse()?;
+            }
+        }
+    }
+    if content_length > MAX_REQUEST_BYTES {
+        eyre::bail!("request is over {} bytes", MAX_REQUEST_BYTES);
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+    Ok((method, path, body))
+}
+
+impl Service {
+    // Signs a claim for the swords in `body`, a `ClaimRequest`. The game has no authoritative server
+    // to check kills against yet, so the per-player limit is what keeps a modified client from
+    // claiming unlimited swords, and colors that can't drop (crafted ones) are never signed.
+    async fn claim(&self, body: &[u8]) -> std::result::Result<ClaimResponse, Rejection> {
+        let request: ClaimRequest = serde_json::from_slice(body).map_err(|e| Rejection(400, e.to_string()))?;
+        if request.colors.is_empty() || request.colors.len() != request.amounts.len() {
+            return Err(Rejection(400, "colors and amounts need the same, non-zero length".to_string()));
+        }
+        for &color in &request.colors {
+            let droppable = self.contract.is_color_droppable(U256::from(color)).call().await
+                .map_err(|e| Rejection(500, format!("couldn't read the color registry: {}", e)))?;
+            if !droppable {
+                return Err(Rejection(400, format!("color {} can't drop", color)));
+            }
+        }
+        let swords = request.amounts.iter().fold(0u64, |total, &amount| total.saturating_add(amount));
+        self.reserve(request.player, swords)?;
+
+        // Read at signing time, so a claim that was never submitted doesn't skip a nonce
+        let nonce = self.contract.drop_nonce(request.player).call().await
+            .map_err(|e| Rejection(500, format!("couldn't read the drop nonce: {}", e)))?;
+        let expiry = unix_time() + CLAIM_TTL_SECS;
+        let signature = self.sign(&request, nonce, expiry)
+            .map_err(|e| Rejection(500, format!("couldn't sign the claim: {}", e)))?;
+        println!("Signed {} swords for {:?} (nonce {})", swords, request.player, nonce);
+        Ok(ClaimResponse { nonce, expiry, signature })
+    }
+
+    // Counts `swords` against the player's limit, or rejects them if they'd go over it
+    fn reserve(&self, player: Address, swords: u64) -> std::result::Result<(), Rejection> {
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let history = recent.entry(player).or_default();
+        while history.front().is_some_and(|&(at, _)| now.duration_since(at) > RATE_WINDOW) {
+            history.pop_front();
+        }
+        let signed: u64 = history.iter().map(|&(_, count)| count).sum();
+        if signed.saturating_add(swords) > self.max_swords_per_minute {
+            return Err(Rejection(429, format!("{:?} already claimed {} swords in the last minute", player, signed)));
+        }
+        history.push_back((now, swords));
+        Ok(())
+    }
+
+    // Signs the claim the way the contract checks it: an `eth_sign` over
+    // `keccak256(abi.encode(contract, chainId, player, colors, amounts, nonce, expiry))`
+    fn sign(&self, request: &ClaimRequest, nonce: U256, expiry: u64) -> Result<Bytes> {
+        let uints = |values: Vec<U256>| Token::Array(values.into_iter().map(Token::Uint).collect());
+        let claim = ethers::abi::encode(&[
+            Token::Address(self.contract.address()),
+            Token::Uint(U256::from(self.chain_id)),
+            Token::Address(request.player),
+            uints(request.colors.iter().map(|&color| U256::from(color)).collect()),
+            uints(request.amounts.iter().map(|&amount| U256::from(amount)).collect()),
+            Token::Uint(nonce),
+            Token::Uint(U256::from(expiry)),
+        ]);
+        let signature = self.signer.sign_hash(hash_message(keccak256(claim)))?;
+        Ok(signature.to_vec().into())
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
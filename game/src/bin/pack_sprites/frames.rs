@@ -0,0 +1,191 @@
+// Picking frames out of `assets/sprites` by file name, and recoloring them into new variants
+
+use eyre::{bail, eyre, Result};
+use image::{Rgba, RgbaImage};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+// The frames of one variant, by row name and then column
+pub type Frames = BTreeMap<String, BTreeMap<u32, RgbaImage>>;
+
+// The frames of each variant that `pattern` picks from `files`. With `rows` set, every matching file
+// has to be in one of them, so a typo in a file name doesn't quietly leave its frame out.
+pub fn group_frames(name: &str, pattern: &str, rows: &[String], sprites_dir: &Path, files: &[String]) -> Result<BTreeMap<String, Frames>> {
+    let mut variants: BTreeMap<String, Frames> = BTreeMap::new();
+    for file in files {
+        let Some(captures) = match_pattern(pattern, file) else {
+            continue;
+        };
+        let variant = captures.get("variant").cloned().unwrap_or_default();
+        let row = captures.get("row").cloned().unwrap_or_default();
+        if !rows.is_empty() && !rows.contains(&row) {
+            bail!("{}: row {:?} isn't one of the rows of sheet {} ({})", file, row, name, rows.join(", "));
+        }
+        let column = match captures.get("frame") {
+            Some(frame) => match frame.parse::<u32>() {
+                Ok(frame) if frame > 0 => frame - 1,
+                _ => bail!("{}: frames are numbered from 1", file),
+            },
+            None => 0,
+        };
+        let image = image::open(sprites_dir.join(file)).map_err(|e| eyre!("{}: {}", file, e))?.to_rgba8();
+        variants.entry(variant).or_default().entry(row).or_default().insert(column, image);
+    }
+
+    // Gaps would shift the clips' frames
+    for (variant, frames) in &variants {
+        for (row, row_frames) in frames {
+            if row_frames.keys().copied().ne(0..row_frames.len() as u32) {
+                bail!("{} {} is missing frames in row {:?}", name, variant, row);
+            }
+        }
+    }
+    Ok(variants)
+}
+
+// What the placeholders of `pattern` stand for in `text`, if it matches. Placeholders take the
+// shortest text that lets the rest match, never cross folders, and `{frame}` only takes digits.
+fn match_pattern(pattern: &str, text: &str) -> Option<HashMap<String, String>> {
+    let Some(start) = pattern.find('{') else {
+        return (pattern == text).then(HashMap::new);
+    };
+    let end = start + pattern[start..].find('}')?;
+    let rest_text = text.strip_prefix(&pattern[..start])?;
+    let placeholder = &pattern[start + 1..end];
+    let rest_pattern = &pattern[end + 1..];
+
+    let ends = rest_text.char_indices().map(|(i, _)| i).skip(1).chain([rest_text.len()]);
+    for value_end in ends {
+        let value = &rest_text[..value_end];
+        if value.contains('/') || (placeholder == "frame" && !value.bytes().all(|b| b.is_ascii_digit())) {
+            break;
+        }
+        if let Some(mut captures) = match_pattern(rest_pattern, &rest_text[value_end..]) {
+            captures.insert(placeholder.to_string(), value.to_string());
+            return Some(captures);
+        }
+    }
+    None
+}
+
+// The frames with every color of `palette` swapped for its new one
+pub fn recolor_frames(frames: &Frames, palette: &BTreeMap<String, String>) -> Result<Frames> {
+    let palette = palette.iter()
+        .map(|(from, to)| Ok((parse_color(from)?, parse_color(to)?)))
+        .collect::<Result<HashMap<_, _>>>()?;
+    let mut recolored = frames.clone();
+    for pixel in recolored.values_mut().flat_map(|row| row.values_mut()).flat_map(|frame| frame.pixels_mut()) {
+        let Rgba([r, g, b, a]) = *pixel;
+        if let Some(&[r, g, b]) = palette.get(&[r, g, b]) {
+            *pixel = Rgba([r, g, b, a]);
+        }
+    }
+    Ok(recolored)
+}
+
+fn parse_color(hex: &str) -> Result<[u8; 3]> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    if digits.len() != 6 || !digits.is_ascii() {
+        bail!("colors look like #rrggbb, not {}", hex);
+    }
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| eyre!("{} isn't a color", hex));
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    // Writes a 2x2 frame of `color` for each of `files` under `dir`
+    fn write_frames(dir: &Path, files: &[&str], color: [u8; 4]) -> Vec<String> {
+        for file in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            RgbaImage::from_pixel(2, 2, Rgba(color)).save(path).unwrap();
+        }
+        files.iter().map(|file| file.to_string()).collect()
+    }
+
+    #[test]
+    fn patterns_capture_their_placeholders() {
+        let captures = match_pattern("swords/{variant}_{row}_{frame}.png", "swords/red_up_3.png").unwrap();
+        assert_eq!(captures["variant"], "red");
+        assert_eq!(captures["row"], "up");
+        assert_eq!(captures["frame"], "3");
+        assert_eq!(match_pattern("items/{variant}.png", "items/blue.png").unwrap()["variant"], "blue");
+        assert!(match_pattern("player.png", "player.png").unwrap().is_empty());
+    }
+
+    #[test]
+    fn placeholders_take_the_shortest_match() {
+        // The variant stops at the first underscore that lets the rest match
+        let captures = match_pattern("{variant}_{row}_{frame}.png", "dark_red_up_1.png").unwrap();
+        assert_eq!(captures["variant"], "dark");
+        assert_eq!(captures["row"], "red_up");
+    }
+
+    #[test]
+    fn patterns_reject_what_doesnt_fit() {
+        assert!(match_pattern("swords/{variant}_{frame}.png", "swords/red_x.png").is_none());
+        // Placeholders don't cross folders
+        assert!(match_pattern("swords/{variant}_{frame}.png", "swords/original/red_1.png").is_none());
+        assert!(match_pattern("swords/{variant}.png", "swords/red.gif").is_none());
+        assert!(match_pattern("player.png", "enemy.png").is_none());
+    }
+
+    #[test]
+    fn frames_group_by_variant_row_and_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = write_frames(dir.path(), &["swords/red_up_1.png", "swords/red_up_2.png", "swords/blue_down_1.png", "other.png"], [255, 0, 0, 255]);
+        let variants = group_frames("sword", "swords/{variant}_{row}_{frame}.png", &rows(&["up", "down"]), dir.path(), &files).unwrap();
+        assert_eq!(variants.keys().collect::<Vec<_>>(), vec!["blue", "red"]);
+        assert_eq!(variants["red"]["up"].keys().copied().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(variants["blue"]["down"].len(), 1);
+    }
+
+    #[test]
+    fn unknown_rows_are_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = write_frames(dir.path(), &["player/up_1.png", "player/uo_1.png"], [0, 0, 0, 255]);
+        let error = group_frames("player", "player/{row}_{frame}.png", &rows(&["up"]), dir.path(), &files).unwrap_err();
+        assert!(error.to_string().contains("player/uo_1.png"));
+        // Without configured rows, every row found is used
+        assert_eq!(group_frames("player", "player/{row}_{frame}.png", &[], dir.path(), &files).unwrap()[""].len(), 2);
+    }
+
+    #[test]
+    fn gaps_and_frame_zero_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let gap = write_frames(dir.path(), &["enemy/chaser_1.png", "enemy/chaser_3.png"], [0, 0, 0, 255]);
+        assert!(group_frames("enemy", "enemy/{variant}_{frame}.png", &[], dir.path(), &gap).is_err());
+        let zero = write_frames(dir.path(), &["items/a_0.png"], [0, 0, 0, 255]);
+        assert!(group_frames("item", "items/{variant}_{frame}.png", &[], dir.path(), &zero).is_err());
+    }
+
+    #[test]
+    fn recoloring_swaps_palette_colors_and_keeps_alpha() {
+        let mut frame = RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 128]));
+        frame.put_pixel(1, 0, Rgba([10, 20, 30, 255]));
+        let frames: Frames = BTreeMap::from([("up".to_string(), BTreeMap::from([(0, frame)]))]);
+        let palette = BTreeMap::from([("#ff0000".to_string(), "#9b1b30".to_string())]);
+        let recolored = recolor_frames(&frames, &palette).unwrap();
+        let frame = &recolored["up"][&0];
+        assert_eq!(*frame.get_pixel(0, 0), Rgba([0x9b, 0x1b, 0x30, 128]));
+        // Colors outside the palette stay
+        assert_eq!(*frame.get_pixel(1, 0), Rgba([10, 20, 30, 255]));
+    }
+
+    #[test]
+    fn colors_parse_with_or_without_a_hash() {
+        assert_eq!(parse_color("#9b1b30").unwrap(), [0x9b, 0x1b, 0x30]);
+        assert_eq!(parse_color("FF0000").unwrap(), [255, 0, 0]);
+        assert!(parse_color("#fff").is_err());
+        assert!(parse_color("#gg0000").is_err());
+        assert!(parse_color("#ééé").is_err());
+        assert!(recolor_frames(&Frames::new(), &BTreeMap::from([("red".to_string(), "#000000".to_string())])).is_err());
+    }
+}
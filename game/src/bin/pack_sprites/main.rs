@@ -0,0 +1,224 @@
+// Packs the loose frames in `assets/sprites` into the sheets the game loads, and writes
+// `assets/animations.json` to match. What goes into each sheet is described by
+// `assets/sprites/packing.json`:
+//
+// - `pattern` picks the frames of a sheet by file name, relative to `assets/sprites`. `{variant}`
+//   (a sword color, an enemy sprite) gets a sheet of its own, `{row}` is the row the frame goes in
+//   and `{frame}` its 1-based column.
+// - `rows` orders the rows, `image` is where each variant's sheet is written and `clips` are the
+//   clips of the sheet, with frames counted within a row. A clip named with `{row}` is repeated for
+//   every row. A file whose row isn't listed is an error, not skipped.
+// - `recolor` makes new variants out of an existing one by swapping palette colors, so a new sword
+//   color is a palette instead of 16 hand-drawn frames.
+//
+// Run from `game/`:
+//
+//     cargo run --bin pack_sprites [assets dir]
+
+mod frames;
+
+use eyre::{bail, eyre, Result};
+use image::{imageops, RgbaImage};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use frames::{group_frames, recolor_frames, Frames};
+
+const DEFAULT_ASSETS_DIR: &str = "assets";
+const CONFIG_PATH: &str = "sprites/packing.json";
+const MANIFEST_PATH: &str = "animations.json";
+
+#[derive(Deserialize)]
+struct PackingConfig {
+    #[serde(default)]
+    recolor: BTreeMap<String, Recolor>,
+    sheets: BTreeMap<String, SheetConfig>,
+}
+
+#[derive(Deserialize)]
+struct Recolor {
+    // Variant the new one is drawn from
+    from: String,
+    // Old color to new color, as "#rrggbb". Alpha is kept.
+    palette: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct SheetConfig {
+    pattern: String,
+    image: String,
+    #[serde(default)]
+    rows: Vec<String>,
+    #[serde(default)]
+    clips: Map<String, Value>,
+}
+
+fn main() -> Result<()> {
+    let assets = PathBuf::from(std::env::args().nth(1).unwrap_or_else(|| DEFAULT_ASSETS_DIR.to_string()));
+    let config: PackingConfig = serde_json::from_str(&std::fs::read_to_string(assets.join(CONFIG_PATH))?)?;
+    let sprites_dir = assets.join("sprites");
+    let mut files = Vec::new();
+    list_files(&sprites_dir, &sprites_dir, &mut files)?;
+    files.sort();
+
+    let mut manifest = Vec::new();
+    for (name, sheet) in &config.sheets {
+        let mut variants = group_frames(name, &sheet.pattern, &sheet.rows, &sprites_dir, &files)?;
+        if variants.is_empty() {
+            bail!("no file matches the pattern of sheet {}: {}", name, sheet.pattern);
+        }
+        for (new_variant, recolor) in &config.recolor {
+            if let Some(frames) = variants.get(&recolor.from) {
+                let recolored = recolor_frames(frames, &recolor.palette)?;
+                variants.insert(new_variant.clone(), recolored);
+            }
+        }
+
+        let rows = row_order(sheet, &variants)?;
+        let columns = variants.values().flat_map(|frames| frames.values()).map(|row| row.len() as u32).max().unwrap_or(1);
+        let (tile_width, tile_height) = tile_size(name, &variants)?;
+        if variants.len() > 1 && !sheet.image.contains("{variant}") {
+            bail!("sheet {} has several variants, but its image path has no {{variant}}", name);
+        }
+
+        for (variant, frames) in &variants {
+            let mut packed = RgbaImage::new(columns * tile_width, rows.len() as u32 * tile_height);
+            for (row_index, row) in rows.iter().enumerate() {
+                let Some(row_frames) = frames.get(row) else {
+                    bail!("{} {} has no frames for row {}", name, variant, row);
+                };
+                for (&column, frame) in row_frames {
+                    let (x, y) = (column * tile_width, row_index as u32 * tile_height);
+                    imageops::replace(&mut packed, frame, x as i64, y as i64);
+                }
+            }
+            let path = assets.join(sheet.image.replace("{variant}", variant));
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            packed.save(&path)?;
+            println!("{}: {}x{} frames", path.display(), columns, rows.len());
+        }
+
+        let clips = expand_clips(name, &sheet.clips, &rows, columns)?;
+        manifest.push((name.clone(), sheet.image.clone(), [tile_width, tile_height], columns, rows.len(), clips));
+    }
+
+    let path = assets.join(MANIFEST_PATH);
+    std::fs::write(&path, write_manifest(&manifest))?;
+    println!("{}: {} sheets", path.display(), manifest.len());
+    Ok(())
+}
+
+// Every file under `dir`, relative to `root` and with `/` between folders
+fn list_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            list_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let parts: Vec<_> = relative.components().map(|part| part.as_os_str().to_string_lossy()).collect();
+            files.push(parts.join("/"));
+        }
+    }
+    Ok(())
+}
+
+// The sheet's rows in order: as configured, or the row names found, sorted
+fn row_order(sheet: &SheetConfig, variants: &BTreeMap<String, Frames>) -> Result<Vec<String>> {
+    if !sheet.rows.is_empty() {
+        return Ok(sheet.rows.clone());
+    }
+    let mut rows: Vec<String> = variants.values().flat_map(|frames| frames.keys().cloned()).collect();
+    rows.sort();
+    rows.dedup();
+    Ok(rows)
+}
+
+// Every frame has to be the same size, since all variants share a layout
+fn tile_size(name: &str, variants: &BTreeMap<String, Frames>) -> Result<(u32, u32)> {
+    let mut frames = variants.values().flat_map(|frames| frames.values()).flat_map(|row| row.values());
+    let size = frames.next().map(|frame| frame.dimensions()).unwrap_or((0, 0));
+    if frames.any(|frame| frame.dimensions() != size) {
+        bail!("the frames of sheet {} aren't all {}x{}", name, size.0, size.1);
+    }
+    Ok(size)
+}
+
+// The clips of the manifest, with `{row}` clips repeated per row and frames counted in the sheet
+fn expand_clips(name: &str, templates: &Map<String, Value>, rows: &[String], columns: u32) -> Result<Vec<(String, Map<String, Value>)>> {
+    let mut clips = Vec::new();
+    for (template_name, template) in templates {
+        let Some(template) = template.as_object() else {
+            bail!("clip {}/{} isn't an object", name, template_name);
+        };
+        let frames = template.get("frames").and_then(Value::as_array)
+            .and_then(|frames| frames.iter().map(Value::as_u64).collect::<Option<Vec<_>>>())
+            .ok_or_else(|| eyre!("clip {}/{} needs a list of frames", name, template_name))?;
+        if let Some(frame) = frames.iter().find(|&&frame| frame >= columns as u64) {
+            bail!("clip {}/{} uses frame {}, but rows have {}", name, template_name, frame, columns);
+        }
+
+        let per_row = template_name.contains("{row}");
+        for (row_index, row) in rows.iter().enumerate() {
+            let offset = row_index as u64 * columns as u64;
+            let mut clip = template.clone();
+            clip.insert("frames".to_string(), frames.iter().map(|frame| Value::from(frame + offset)).collect());
+            clips.push((template_name.replace("{row}", row), clip));
+            if !per_row {
+                break;
+            }
+        }
+    }
+    Ok(clips)
+}
+
+type ManifestSheet = (String, String, [u32; 2], u32, usize, Vec<(String, Map<String, Value>)>);
+
+// Laid out by hand so clips stay one per line
+fn write_manifest(sheets: &[ManifestSheet]) -> String {
+    let mut out = String::from("{\n  \"sheets\": {\n");
+    for (i, (name, image, tile_size, columns, rows, clips)) in sheets.iter().enumerate() {
+        out += &format!("    {}: {{\n", Value::from(name.as_str()));
+        out += &format!("      \"image\": {},\n", Value::from(image.as_str()));
+        out += &format!("      \"tile_size\": [{}, {}],\n", tile_size[0], tile_size[1]);
+        out += &format!("      \"columns\": {},\n", columns);
+        out += &format!("      \"rows\": {},\n", rows);
+        if clips.is_empty() {
+            out += "      \"clips\": {}\n";
+        } else {
+            out += "      \"clips\": {\n";
+            for (j, (clip_name, clip)) in clips.iter().enumerate() {
+                let comma = if j + 1 < clips.len() { "," } else { "" };
+                out += &format!("        {}: {}{}\n", Value::from(clip_name.as_str()), inline_clip(clip), comma);
+            }
+            out += "      }\n";
+        }
+        out += if i + 1 < sheets.len() { "    },\n" } else { "    }\n" };
+    }
+    out += "  }\n}\n";
+    out
+}
+
+// `{ "frames": [0, 1], "fps": 8 }`, frames first
+fn inline_clip(clip: &Map<String, Value>) -> String {
+    let fields = clip.get("frames").map(|frames| ("frames", frames)).into_iter()
+        .chain(clip.iter().filter(|(key, _)| *key != "frames").map(|(key, value)| (key.as_str(), value)))
+        .map(|(key, value)| format!("{}: {}", Value::from(key), inline(value)))
+        .collect::<Vec<_>>();
+    format!("{{ {} }}", fields.join(", "))
+}
+
+fn inline(value: &Value) -> String {
+    match value {
+        Value::Array(items) => format!("[{}]", items.iter().map(inline).collect::<Vec<_>>().join(", ")),
+        Value::Object(fields) if fields.is_empty() => "{}".to_string(),
+        Value::Object(fields) => {
+            let fields = fields.iter().map(|(key, value)| format!("{}: {}", Value::from(key.as_str()), inline(value)));
+            format!("{{ {} }}", fields.collect::<Vec<_>>().join(", "))
+        }
+        _ => value.to_string(),
+    }
+}
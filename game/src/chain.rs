@@ -0,0 +1,357 @@
+// Background chain worker: Bevy systems hand it transaction requests over a channel and get the
+// results back as events, so gameplay never waits on the network. Where the swords actually go is
+// up to the `ChainBackend` it runs: the contract over RPC, an in-memory inventory or a save file.
+
+use bevy::prelude::*;
+use ethers::types::TxHash;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tokio::{runtime::Runtime, sync::mpsc, task::JoinHandle};
+
+use watcher::InventoryWatcher;
+
+pub mod local;
+pub mod rpc;
+pub mod watcher;
+
+// A sword color from the contract's color registry. Its index in the registry is its on-chain id.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SwordColor {
+    pub name: String,
+    pub weight: u32,
+    pub enabled: bool,
+}
+
+// The colors a fresh contract registers, used whenever there's no registry to read
+pub fn default_sword_colors() -> Vec<SwordColor> {
+    ["red", "blue", "green"].into_iter()
+        .map(|name| SwordColor { name: name.to_string(), weight: 1, enabled: true })
+        .collect()
+}
+
+// Where a recorded sword ended up
+pub struct Receipt {
+    pub tx_hash: Option<TxHash>, // None for backends that don't send transactions
+    pub block: Option<u64>,
+}
+
+// Why swords couldn't be recorded
+#[derive(Debug)]
+pub struct CollectError {
+    pub error: String,
+    // A transaction that was broadcast but whose outcome isn't known, like one whose receipt never
+    // came. Its swords may still be minted, so it has to be looked up before they're sent again.
+    pub unconfirmed: Option<TxHash>,
+}
+
+impl From<String> for CollectError {
+    fn from(error: String) -> Self {
+        CollectError { error, unconfirmed: None }
+    }
+}
+
+// What became of a transaction a failed request broadcast
+pub enum TxStatus {
+    Mined(Receipt),
+    // Mined without minting anything
+    Reverted,
+    // Still waiting to be mined
+    Pending,
+    // Gone from the node, so it can't be mined any more once its nonce is reused
+    Dropped,
+}
+
+// Storage for the player's swords. Every `SwordCollection` call the game makes goes through this.
+pub trait ChainBackend: Send + 'static {
+    // Short name shown to the player
+    fn name(&self) -> &'static str;
+
+    // Reads the color registry
+    fn load_colors(&mut self) -> impl Future<Output = Result<Vec<SwordColor>>> + Send;
+
+    // Reads how many swords of each color id in `0..colors` the player owns
+    fn load_inventory(&mut self, colors: usize) -> impl Future<Output = Result<Vec<u64>>> + Send;
+
+    // Records collected swords given as `(color, amount)` pairs. `on_submitted` is called with each
+    // transaction hash as soon as it's broadcast, for backends that send them.
+    fn collect_swords(
+        &mut self,
+        swords: &[(u8, u64)],
+        on_submitted: impl FnMut(TxHash) + Send,
+    ) -> impl Future<Output = std::result::Result<Receipt, CollectError>> + Send;
+
+    // Looks up a transaction from a `CollectError`. Backends that don't send transactions never
+    // report one.
+    fn transaction_status(&mut self, _tx_hash: TxHash) -> impl Future<Output = Result<TxStatus>> + Send {
+        async { Ok(TxStatus::Dropped) }
+    }
+}
+
+// A transaction the game wants sent
+#[derive(Clone, Debug)]
+pub enum ChainRequest {
+    // Mints a batch of swords as `(color, amount)` pairs
+    CollectSwords { swords: Vec<(u8, u64)> },
+    // Uploads swords that were collected while playing offline
+    SyncSwords { swords: Vec<(u8, u64)> },
+    // Reads how many swords of each color id in `0..colors` the player owns
+    LoadInventory { colors: usize },
+}
+
+impl ChainRequest {
+    // The swords the request mints, if any
+    pub fn swords(&self) -> &[(u8, u64)] {
+        match self {
+            ChainRequest::CollectSwords { swords } | ChainRequest::SyncSwords { swords } => swords,
+            ChainRequest::LoadInventory { .. } => &[],
+        }
+    }
+}
+
+// Tells apart the requests sent to the worker, including retries of the same request
+pub type RequestId = u64;
+
+// What happened to a request, delivered to Bevy systems as an event
+#[derive(Event, Clone, Debug)]
+pub enum ChainEvent {
+    Submitted { id: RequestId, request: ChainRequest, tx_hash: TxHash },
+    Confirmed { id: RequestId, request: ChainRequest, tx_hash: Option<TxHash>, block: Option<u64> },
+    // `unconfirmed` is the transaction that may still have minted the swords, as in `CollectError`
+    Failed { id: RequestId, request: ChainRequest, error: String, unconfirmed: Option<TxHash> },
+    // Answers a `LoadInventory` request
+    InventoryLoaded { counts: Vec<u64> },
+    // Swords that moved in or out of the player's wallet in transaction `tx_hash`, as seen by the
+    // watcher, given as `(color, change)` pairs
+    InventoryChanged { tx_hash: TxHash, block: u64, changes: Vec<(u8, i64)> },
+    // The chain dropped blocks the watcher had read up to `block`, so the counts need reading again
+    Reorged { block: u64 },
+}
+
+#[derive(Resource)]
+pub struct ChainWorker {
+    pub backend_name: &'static str,
+    requests: mpsc::UnboundedSender<Job>,
+    next_id: AtomicU64,
+    events: mpsc::UnboundedReceiver<ChainEvent>,
+    // For tasks besides the worker that report chain events, like the watcher
+    event_sender: mpsc::UnboundedSender<ChainEvent>,
+    watching: bool,
+    task: JoinHandle<()>,
+    // Kept alive for as long as the game runs; the worker task lives on it
+    runtime: Runtime,
+}
+
+impl ChainWorker {
+    pub fn spawn<B: ChainBackend>(runtime: Runtime, backend: B) -> Self {
+        let backend_name = backend.name();
+        let (request_sender, request_receiver) = mpsc::unbounded_channel();
+        let (event_sender, event_receiver) = mpsc::unbounded_channel();
+        let task = runtime.spawn(run_worker(backend, request_receiver, event_sender.clone()));
+        ChainWorker {
+            backend_name,
+            requests: request_sender,
+            next_id: AtomicU64::new(0),
+            events: event_receiver,
+            event_sender,
+            watching: false,
+            task,
+            runtime,
+        }
+    }
+
+    // Queues a transaction without waiting for it. Its events will carry the returned id.
+    pub fn send(&self, request: ChainRequest) -> RequestId {
+        self.retry(request, None)
+    }
+
+    // Queues a failed request again. With the `unconfirmed` transaction of its failure, that one is
+    // looked up first, and the swords are only sent again if it can no longer mint them.
+    pub fn retry(&self, request: ChainRequest, unconfirmed: Option<TxHash>) -> RequestId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        if self.requests.send(Job { id, request, unconfirmed }).is_err() {
+            eprintln!("Chain worker stopped, dropping transaction");
+        }
+        id
+    }
+
+    // Runs `watcher` alongside the worker, reporting into the same event queue. A transaction's
+    // `Submitted` is queued as it's broadcast, so it always comes before the watcher sees its logs.
+    pub fn spawn_watcher(&mut self, watcher: InventoryWatcher) {
+        self.runtime.spawn(watcher.run(self.event_sender.clone()));
+        self.watching = true;
+    }
+
+    // Whether a watcher reports `InventoryChanged` events
+    pub fn is_watching(&self) -> bool {
+        self.watching
+    }
+
+    // Lets the worker finish the requests already queued, giving up after `timeout`. Returns the
+    // events that no frame is left to read.
+    pub fn shutdown(self, timeout: Duration) -> Vec<ChainEvent> {
+        let ChainWorker { requests, mut events, task, runtime, .. } = self;
+        drop(requests);
+        // The timeout's timer has to be made inside the runtime
+        if runtime.block_on(async { tokio::time::timeout(timeout, task).await }).is_err() {
+            eprintln!("Chain worker didn't finish in time, some swords may not have been saved");
+        }
+        let mut remaining = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            remaining.push(event);
+        }
+        remaining
+    }
+}
+
+// Moves whatever the worker reported since the last frame into Bevy's event queue
+pub fn forward_chain_events(mut worker: ResMut<ChainWorker>, mut events: EventWriter<ChainEvent>) {
+    while let Ok(event) = worker.events.try_recv() {
+        events.send(event);
+    }
+}
+
+// A request queued for the worker
+struct Job {
+    id: RequestId,
+    request: ChainRequest,
+    unconfirmed: Option<TxHash>,
+}
+
+async fn run_worker<B: ChainBackend>(
+    mut backend: B,
+    mut requests: mpsc::UnboundedReceiver<Job>,
+    events: mpsc::UnboundedSender<ChainEvent>,
+) {
+    // Requests are handled one at a time so transactions go out in order
+    while let Some(Job { id, request, unconfirmed }) = requests.recv().await {
+        if let ChainRequest::LoadInventory { colors } = request {
+            let event = match backend.load_inventory(colors).await {
+                Ok(counts) => ChainEvent::InventoryLoaded { counts },
+                Err(e) => ChainEvent::Failed { id, request, error: e.to_string(), unconfirmed: None },
+            };
+            let _ = events.send(event);
+            continue;
+        }
+
+        // A retry after an unclear failure only resends if the first transaction can't mint anymore
+        if let Some(tx_hash) = unconfirmed {
+            let settled = match backend.transaction_status(tx_hash).await {
+                Ok(TxStatus::Mined(receipt)) => Some(ChainEvent::Confirmed { id, request: request.clone(), tx_hash: receipt.tx_hash, block: receipt.block }),
+                Ok(TxStatus::Reverted | TxStatus::Dropped) => None,
+                Ok(TxStatus::Pending) => Some(ChainEvent::Failed {
+                    id,
+                    request: request.clone(),
+                    error: format!("transaction {:?} is still pending", tx_hash),
+                    unconfirmed,
+                }),
+                Err(e) => Some(ChainEvent::Failed {
+                    id,
+                    request: request.clone(),
+                    error: format!("couldn't look up transaction {:?}: {}", tx_hash, e),
+                    unconfirmed,
+                }),
+            };
+            if let Some(event) = settled {
+                let _ = events.send(event);
+                continue;
+            }
+        }
+
+        let submitted = {
+            let events = events.clone();
+            let request = request.clone();
+            move |tx_hash| {
+                let _ = events.send(ChainEvent::Submitted { id, request: request.clone(), tx_hash });
+            }
+        };
+        let event = match backend.collect_swords(request.swords(), submitted).await {
+            Ok(receipt) => ChainEvent::Confirmed { id, request, tx_hash: receipt.tx_hash, block: receipt.block },
+            Err(CollectError { error, unconfirmed }) => ChainEvent::Failed { id, request, error, unconfirmed },
+        };
+        let _ = events.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{atomic::AtomicUsize, Arc};
+
+    const LOST_TX: TxHash = TxHash::repeat_byte(7);
+
+    // Loses the receipt of every transaction it sends, and reports `status` when one is looked up
+    struct LosingBackend {
+        sent: Arc<AtomicUsize>,
+        status: fn() -> TxStatus,
+    }
+
+    impl ChainBackend for LosingBackend {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        async fn load_colors(&mut self) -> Result<Vec<SwordColor>> {
+            Ok(default_sword_colors())
+        }
+
+        async fn load_inventory(&mut self, colors: usize) -> Result<Vec<u64>> {
+            Ok(vec![0; colors])
+        }
+
+        async fn collect_swords(
+            &mut self,
+            _swords: &[(u8, u64)],
+            mut on_submitted: impl FnMut(TxHash) + Send,
+        ) -> std::result::Result<Receipt, CollectError> {
+            self.sent.fetch_add(1, Ordering::Relaxed);
+            on_submitted(LOST_TX);
+            Err(CollectError { error: "receipt lost".to_string(), unconfirmed: Some(LOST_TX) })
+        }
+
+        async fn transaction_status(&mut self, _tx_hash: TxHash) -> Result<TxStatus> {
+            Ok((self.status)())
+        }
+    }
+
+    // Sends a request, retries it after its failure, and returns how many times it went out and what
+    // the retry ended in
+    fn retry_lost_receipt(status: fn() -> TxStatus) -> (usize, ChainEvent) {
+        let sent = Arc::new(AtomicUsize::new(0));
+        let worker = ChainWorker::spawn(Runtime::new().unwrap(), LosingBackend { sent: sent.clone(), status });
+        let request = ChainRequest::CollectSwords { swords: vec![(1, 2)] };
+        worker.send(request.clone());
+        let retry = worker.retry(request, Some(LOST_TX));
+
+        let events = worker.shutdown(Duration::from_secs(5));
+        assert!(events.iter().any(|event| matches!(event, ChainEvent::Failed { unconfirmed: Some(tx_hash), .. } if *tx_hash == LOST_TX)));
+        let outcome = events.into_iter()
+            .rfind(|event| matches!(event, ChainEvent::Confirmed { id, .. } | ChainEvent::Failed { id, .. } if *id == retry))
+            .unwrap();
+        (sent.load(Ordering::Relaxed), outcome)
+    }
+
+    #[test]
+    fn retry_confirms_a_mined_transaction_without_resending() {
+        let (sent, outcome) = retry_lost_receipt(|| TxStatus::Mined(Receipt { tx_hash: Some(LOST_TX), block: Some(12) }));
+        assert_eq!(sent, 1);
+        assert!(matches!(outcome, ChainEvent::Confirmed { tx_hash: Some(tx_hash), block: Some(12), .. } if tx_hash == LOST_TX));
+    }
+
+    #[test]
+    fn retry_waits_for_a_pending_transaction() {
+        let (sent, outcome) = retry_lost_receipt(|| TxStatus::Pending);
+        assert_eq!(sent, 1);
+        assert!(matches!(outcome, ChainEvent::Failed { unconfirmed: Some(tx_hash), .. } if tx_hash == LOST_TX));
+    }
+
+    #[test]
+    fn retry_resends_a_dropped_or_reverted_transaction() {
+        assert_eq!(retry_lost_receipt(|| TxStatus::Dropped).0, 2);
+        assert_eq!(retry_lost_receipt(|| TxStatus::Reverted).0, 2);
+    }
+}
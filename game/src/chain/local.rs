@@ -0,0 +1,229 @@
+// `ChainBackend`s that keep the swords on this machine, so the game plays fully offline. The save
+// file also remembers which swords never reached the chain, so they can be synced later.
+
+use ethers::types::TxHash;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use super::{default_sword_colors, ChainBackend, CollectError, Receipt, SwordColor};
+
+pub const DEFAULT_SAVE_PATH: &str = "save/inventory.json";
+
+// The player's swords as stored locally, indexed by color id
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LocalInventory {
+    pub colors: Vec<SwordColor>,
+    pub swords: Vec<u64>,
+    // Swords collected offline that haven't been synced to the chain yet
+    pub unsynced: Vec<u64>,
+}
+
+impl Default for LocalInventory {
+    fn default() -> Self {
+        let colors = default_sword_colors();
+        LocalInventory {
+            swords: vec![0; colors.len()],
+            unsynced: vec![0; colors.len()],
+            colors,
+        }
+    }
+}
+
+impl LocalInventory {
+    fn add_swords(&mut self, swords: &[(u8, u64)]) -> std::result::Result<(), String> {
+        if let Some(&(color, _)) = swords.iter().find(|&&(color, _)| color as usize >= self.colors.len()) {
+            return Err(format!("color {} is not a sword color", color));
+        }
+        // Older saves may have been written before some colors existed
+        self.swords.resize(self.colors.len(), 0);
+        self.unsynced.resize(self.colors.len(), 0);
+        for &(color, amount) in swords {
+            self.swords[color as usize] += amount;
+            self.unsynced[color as usize] += amount;
+        }
+        Ok(())
+    }
+
+    fn counts(&self, colors: usize) -> Vec<u64> {
+        (0..colors).map(|color| self.swords.get(color).copied().unwrap_or(0)).collect()
+    }
+}
+
+// Keeps the swords in memory only; they're gone when the game closes
+#[derive(Default)]
+pub struct MemoryBackend {
+    inventory: LocalInventory,
+}
+
+impl ChainBackend for MemoryBackend {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn load_colors(&mut self) -> Result<Vec<SwordColor>> {
+        Ok(self.inventory.colors.clone())
+    }
+
+    async fn load_inventory(&mut self, colors: usize) -> Result<Vec<u64>> {
+        Ok(self.inventory.counts(colors))
+    }
+
+    async fn collect_swords(
+        &mut self,
+        swords: &[(u8, u64)],
+        _on_submitted: impl FnMut(TxHash) + Send,
+    ) -> std::result::Result<Receipt, CollectError> {
+        self.inventory.add_swords(swords)?;
+        Ok(Receipt { tx_hash: None, block: None })
+    }
+}
+
+// A JSON save file holding a `LocalInventory`
+pub struct LocalSave {
+    path: PathBuf,
+    pub inventory: LocalInventory,
+}
+
+impl LocalSave {
+    // Opens the save at `path`, starting a new one if it doesn't exist yet
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let inventory = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => LocalInventory::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(LocalSave { path, inventory })
+    }
+
+    pub fn write(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Write to a temporary file first so a crash can't leave a half-written save behind
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(&self.inventory)?)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+
+    // The swords waiting to be synced as `(color, amount)` pairs
+    pub fn unsynced_swords(&self) -> Vec<(u8, u64)> {
+        self.inventory.unsynced.iter().enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(color, &count)| (color as u8, count))
+            .collect()
+    }
+
+    // Records that `swords` made it to the chain
+    pub fn mark_synced(&mut self, swords: &[(u8, u64)]) -> Result<()> {
+        for &(color, amount) in swords {
+            if let Some(count) = self.inventory.unsynced.get_mut(color as usize) {
+                *count = count.saturating_sub(amount);
+            }
+        }
+        self.write()
+    }
+}
+
+// Keeps the swords in a save file on disk
+pub struct FileBackend {
+    save: LocalSave,
+}
+
+impl FileBackend {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(FileBackend { save: LocalSave::open(path)? })
+    }
+}
+
+impl ChainBackend for FileBackend {
+    fn name(&self) -> &'static str {
+        "local save"
+    }
+
+    async fn load_colors(&mut self) -> Result<Vec<SwordColor>> {
+        Ok(self.save.inventory.colors.clone())
+    }
+
+    async fn load_inventory(&mut self, colors: usize) -> Result<Vec<u64>> {
+        Ok(self.save.inventory.counts(colors))
+    }
+
+    async fn collect_swords(
+        &mut self,
+        swords: &[(u8, u64)],
+        _on_submitted: impl FnMut(TxHash) + Send,
+    ) -> std::result::Result<Receipt, CollectError> {
+        self.save.inventory.add_swords(swords)?;
+        self.save.write().map_err(|e| format!("failed to write save file: {}", e))?;
+        Ok(Receipt { tx_hash: None, block: None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn save_round_trips_through_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("save/inventory.json");
+
+        // A missing save starts empty, and the directory is made on the first write
+        let mut backend = FileBackend::open(&path).unwrap();
+        assert_eq!(backend.load_inventory(3).await.unwrap(), vec![0, 0, 0]);
+        backend.collect_swords(&[(0, 2), (2, 1)], |_| {}).await.unwrap();
+        backend.collect_swords(&[(0, 1)], |_| {}).await.unwrap();
+
+        // Written through the temporary file, which is renamed over the save
+        assert!(path.exists());
+        assert!(!path.with_extension("json.tmp").exists());
+
+        let mut save = LocalSave::open(&path).unwrap();
+        assert_eq!(save.inventory.swords, vec![3, 0, 1]);
+        assert_eq!(save.unsynced_swords(), vec![(0, 3), (2, 1)]);
+
+        // Syncing only clears what made it to the chain; the swords themselves stay
+        save.mark_synced(&[(0, 2), (2, 1)]).unwrap();
+        let save = LocalSave::open(&path).unwrap();
+        assert_eq!(save.inventory.swords, vec![3, 0, 1]);
+        assert_eq!(save.unsynced_swords(), vec![(0, 1)]);
+    }
+
+    #[tokio::test]
+    async fn bad_color_leaves_the_save_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("inventory.json");
+        let mut backend = FileBackend::open(&path).unwrap();
+        backend.collect_swords(&[(1, 1)], |_| {}).await.unwrap();
+
+        assert!(backend.collect_swords(&[(1, 1), (9, 1)], |_| {}).await.is_err());
+        assert_eq!(LocalSave::open(&path).unwrap().inventory.swords, vec![0, 1, 0]);
+    }
+
+    #[tokio::test]
+    async fn older_saves_grow_to_new_colors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("inventory.json");
+        let inventory = LocalInventory { swords: vec![4], unsynced: vec![], ..Default::default() };
+        std::fs::write(&path, serde_json::to_string(&inventory).unwrap()).unwrap();
+
+        let mut backend = FileBackend::open(&path).unwrap();
+        assert_eq!(backend.load_inventory(3).await.unwrap(), vec![4, 0, 0]);
+        backend.collect_swords(&[(2, 1)], |_| {}).await.unwrap();
+
+        let save = LocalSave::open(&path).unwrap();
+        assert_eq!(save.inventory.swords, vec![4, 0, 1]);
+        assert_eq!(save.unsynced_swords(), vec![(2, 1)]);
+    }
+
+    #[test]
+    fn corrupt_save_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("inventory.json");
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(LocalSave::open(&path).is_err());
+    }
+}
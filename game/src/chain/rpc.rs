@@ -0,0 +1,322 @@
+// `ChainBackend` that keeps the swords in the `SwordCollection` contract, talking to it over RPC.
+// Manages the account nonce itself so transactions can be retried without stalling the queue.
+
+use ethers::prelude::{abigen, ContractError, Http, LocalWallet, Middleware, PendingTransaction, Provider, ProviderError, SignerMiddleware};
+use ethers::signers::Signer;
+use ethers::{
+    types::{Address, BlockNumber, Bytes, TxHash, H256, U256},
+    utils::keccak256,
+};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::PathBuf, sync::Arc, time::Duration};
+
+use super::watcher::InventoryWatcher;
+use super::{ChainBackend, CollectError, Receipt, SwordColor, TxStatus};
+
+// Generate the contract bindings
+abigen!(
+    SwordCollection,
+    r#"[
+        function number() external view returns (uint256)
+        function increment() external
+        function getSwordCount(uint256 color) external view returns (uint256)
+        function getSwordCountOf(address player, uint256 color) external view returns (uint256)
+        function getSwordCountsOf(address player) external view returns (uint256, uint256, uint256)
+        function getSwordCountsBatch(address player, uint256[] colors) external view returns (uint256[])
+        function getColors() external view returns (string[], uint256[], bool[])
+        function incrementSword(uint256 color) external
+        function incrementSwords(uint256[] colors, uint256[] amounts) external
+        function dropNonce(address player) external view returns (uint256)
+        function hasRole(bytes32 role, address account) external view returns (bool)
+        function claimSwords(uint256[] colors, uint256[] amounts, uint256 nonce, uint256 expiry, bytes signature) external
+        error InvalidColor(uint256 color)
+        error Overflow()
+        error AccessControlUnauthorizedAccount(address account, bytes32 neededRole)
+        error InvalidArrayLength(uint256 colorsLength, uint256 amountsLength)
+        error InvalidDropSignature()
+        error DropExpired(uint256 expiry, uint256 timestamp)
+        error InvalidDropNonce(uint256 expected, uint256 provided)
+        error ColorNotDroppable(uint256 color)
+        event TransferSingle(address indexed operator, address indexed from, address indexed to, uint256 id, uint256 value)
+        event TransferBatch(address indexed operator, address indexed from, address indexed to, uint256[] ids, uint256[] values)
+    ]"#
+);
+
+pub type Client = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+const MAX_SEND_ATTEMPTS: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_millis(500); // Grows linearly with each attempt
+
+// Connection settings read from the environment
+pub struct RpcConfig {
+    pub rpc_url: String,
+    pub contract_address: String,
+    pub wallet: WalletSource,
+    // Base URL of the drop signing service (`cargo run --bin drop_signer`), which signs the claims
+    // for the swords each flush mints. Without it the wallet has to be a minter.
+    pub drop_signer_url: Option<String>,
+}
+
+impl RpcConfig {
+    // Returns None unless RPC_URL, STYLUS_CONTRACT_ADDRESS and either KEYSTORE_PATH or PRIVATE_KEY
+    // are set. The keystore wins when both are.
+    pub fn from_env() -> Option<Self> {
+        let var = |name| std::env::var(name).ok().filter(|value: &String| !value.trim().is_empty());
+        let wallet = match var("KEYSTORE_PATH") {
+            Some(path) => WalletSource::Keystore(PathBuf::from(path)),
+            None => WalletSource::PrivateKey(var("PRIVATE_KEY")?),
+        };
+        Some(RpcConfig {
+            rpc_url: var("RPC_URL")?,
+            contract_address: var("STYLUS_CONTRACT_ADDRESS")?,
+            wallet,
+            drop_signer_url: var("DROP_SIGNER_URL"),
+        })
+    }
+}
+
+// Where the player's key comes from
+pub enum WalletSource {
+    // Encrypted JSON keystore, unlocked with a password the player types in
+    Keystore(PathBuf),
+    // Plaintext key, only meant for local dev nodes
+    PrivateKey(String),
+}
+
+// The wallet has no way to mint swords: it isn't a minter and there's no drop signing service to
+// claim them through
+#[derive(Debug)]
+pub struct CannotMint(pub Address);
+
+impl fmt::Display for CannotMint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "wallet {:?} can't mint swords: set DROP_SIGNER_URL to a drop signing service, or have an admin grant it MINTER_ROLE", self.0)
+    }
+}
+
+impl std::error::Error for CannotMint {}
+
+// A batch drop claim, as the drop signing service sends it
+#[derive(Deserialize)]
+struct DropClaim {
+    nonce: U256,
+    expiry: u64,
+    signature: Bytes,
+}
+
+#[derive(Serialize)]
+struct ClaimRequest<'a> {
+    player: Address,
+    colors: &'a [u8],
+    amounts: &'a [u64],
+}
+
+pub struct RpcBackend {
+    client: Arc<Client>,
+    contract: SwordCollection<Client>,
+    http: reqwest::Client,
+    drop_signer_url: Option<String>,
+    // Next account nonce to use, or None to re-read it from the node
+    tx_nonce: Option<U256>,
+    // Number and hash of the block the inventory was last read at
+    inventory_block: Option<(u64, H256)>,
+}
+
+impl RpcBackend {
+    // Fails with `CannotMint` when the wallet has neither a drop signing service nor MINTER_ROLE
+    pub async fn connect(config: &RpcConfig, wallet: LocalWallet) -> Result<Self> {
+        let provider = Provider::<Http>::try_from(config.rpc_url.as_str())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let client = Arc::new(SignerMiddleware::new(
+            provider,
+            wallet.with_chain_id(chain_id),
+        ));
+        let contract_address: Address = config.contract_address.parse()?;
+        let contract = SwordCollection::new(contract_address, client.clone());
+
+        if config.drop_signer_url.is_none() && !contract.has_role(keccak256("MINTER_ROLE"), client.address()).call().await? {
+            return Err(CannotMint(client.address()).into());
+        }
+        Ok(RpcBackend {
+            client,
+            contract,
+            http: reqwest::Client::new(),
+            drop_signer_url: config.drop_signer_url.as_ref().map(|url| url.trim_end_matches('/').to_string()),
+            tx_nonce: None,
+            inventory_block: None,
+        })
+    }
+
+    // A watcher for this wallet's swords, polling every `interval` from the block the inventory was
+    // read at. None until the inventory has been read.
+    pub fn watcher(&self, interval: Duration) -> Option<InventoryWatcher> {
+        let inventory_block = self.inventory_block?;
+        Some(InventoryWatcher::new(self.client.provider().clone(), self.contract.address(), self.client.address(), interval, inventory_block))
+    }
+
+    // Signs and broadcasts one transaction minting `swords`, as `(color, amount)` pairs, returning its
+    // hash. With a drop signing service it's a batch claim signed by it, otherwise the wallet is a
+    // minter.
+    async fn send(&mut self, swords: &[(u8, u64)]) -> std::result::Result<TxHash, ContractError<Client>> {
+        let client = &self.client;
+        let nonce = match self.tx_nonce {
+            Some(nonce) => nonce,
+            None => client
+                .get_transaction_count(client.address(), Some(BlockNumber::Pending.into()))
+                .await
+                .map_err(|e| ContractError::MiddlewareError { e })?,
+        };
+
+        let colors: Vec<U256> = swords.iter().map(|&(color, _)| U256::from(color)).collect();
+        let amounts: Vec<U256> = swords.iter().map(|&(_, amount)| U256::from(amount)).collect();
+        let call = match &self.drop_signer_url {
+            Some(url) => {
+                let claim = self.request_claim(url, swords).await
+                    .map_err(|e| ContractError::ProviderError { e: ProviderError::CustomError(format!("drop signing service: {}", e)) })?;
+                self.contract.claim_swords(colors, amounts, claim.nonce, U256::from(claim.expiry), claim.signature)
+            }
+            None => self.contract.increment_swords(colors, amounts),
+        };
+
+        let tx_hash = call.nonce(nonce).send().await?.tx_hash();
+        self.tx_nonce = Some(nonce + 1);
+        Ok(tx_hash)
+    }
+
+    // Sends `swords` in one transaction, retrying transient failures, and waits for it to be mined
+    async fn transact(
+        &mut self,
+        swords: &[(u8, u64)],
+        on_submitted: &mut (impl FnMut(TxHash) + Send),
+    ) -> std::result::Result<Receipt, CollectError> {
+        let mut attempt = 1;
+        let tx_hash = loop {
+            match self.send(swords).await {
+                Ok(tx_hash) => break tx_hash,
+                // Reverts will fail the same way again, anything else is worth another try
+                Err(e) if attempt < MAX_SEND_ATTEMPTS && e.as_revert().is_none() => {
+                    eprintln!("Sending transaction failed (attempt {}), retrying: {}", attempt, e);
+                    self.tx_nonce = None;
+                    tokio::time::sleep(RETRY_BACKOFF * attempt).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    self.tx_nonce = None;
+                    return Err(describe_contract_error(&e).into());
+                }
+            }
+        };
+        on_submitted(tx_hash);
+
+        // Once submitted the transaction is never resent here. When it isn't clear whether it was
+        // mined, the error says which it was, so a retry can look it up instead of minting twice.
+        let unconfirmed = |error| CollectError { error, unconfirmed: Some(tx_hash) };
+        match PendingTransaction::new(tx_hash, self.client.provider()).await {
+            Ok(Some(receipt)) if receipt.status == Some(1.into()) => Ok(Receipt {
+                tx_hash: Some(tx_hash),
+                block: receipt.block_number.map(|block| block.as_u64()),
+            }),
+            Ok(Some(_)) => Err(format!("transaction {:?} reverted", tx_hash).into()),
+            Ok(None) => {
+                self.tx_nonce = None;
+                Err(unconfirmed(format!("transaction {:?} was dropped", tx_hash)))
+            }
+            Err(e) => Err(unconfirmed(e.to_string())),
+        }
+    }
+
+    // Asks the drop signing service at `url` for a claim to `swords`
+    async fn request_claim(&self, url: &str, swords: &[(u8, u64)]) -> std::result::Result<DropClaim, String> {
+        let request = ClaimRequest {
+            player: self.client.address(),
+            colors: &swords.iter().map(|&(color, _)| color).collect::<Vec<_>>(),
+            amounts: &swords.iter().map(|&(_, amount)| amount).collect::<Vec<_>>(),
+        };
+        let response = self.http.post(format!("{}/claim", url)).json(&request).send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error = response.json::<serde_json::Value>().await.ok()
+                .and_then(|body| body["error"].as_str().map(str::to_string))
+                .unwrap_or_else(|| status.to_string());
+            return Err(error);
+        }
+        response.json().await.map_err(|e| e.to_string())
+    }
+}
+
+impl ChainBackend for RpcBackend {
+    fn name(&self) -> &'static str {
+        "chain"
+    }
+
+    async fn load_colors(&mut self) -> Result<Vec<SwordColor>> {
+        let (names, weights, enabled) = self.contract.get_colors().call().await?;
+        Ok(names.into_iter().zip(weights).zip(enabled)
+            .map(|((name, weight), enabled)| SwordColor {
+                name,
+                weight: weight.min(U256::from(u32::MAX)).as_u32(),
+                enabled,
+            })
+            .collect())
+    }
+
+    async fn load_inventory(&mut self, colors: usize) -> Result<Vec<u64>> {
+        // Loads the swords owned by our own wallet in a single call, pinned to one block so the
+        // watcher can pick up right after it
+        let block = self.client.get_block(BlockNumber::Latest).await?
+            .ok_or_else(|| eyre::eyre!("the node has no latest block"))?;
+        let (Some(number), Some(hash)) = (block.number, block.hash) else {
+            eyre::bail!("the latest block is still pending");
+        };
+        let color_ids = (0..colors).map(U256::from).collect();
+        let counts = self.contract.get_sword_counts_batch(self.client.address(), color_ids).block(hash).call().await?;
+        self.inventory_block = Some((number.as_u64(), hash));
+        Ok(counts.into_iter().map(|count| count.min(U256::from(u64::MAX)).as_u64()).collect())
+    }
+
+    async fn collect_swords(
+        &mut self,
+        swords: &[(u8, u64)],
+        mut on_submitted: impl FnMut(TxHash) + Send,
+    ) -> std::result::Result<Receipt, CollectError> {
+        // The whole batch goes out in one transaction, so it's either minted or not at all
+        self.transact(swords, &mut on_submitted).await
+    }
+
+    async fn transaction_status(&mut self, tx_hash: TxHash) -> Result<TxStatus> {
+        let provider = self.client.provider();
+        if let Some(receipt) = provider.get_transaction_receipt(tx_hash).await? {
+            if receipt.status != Some(1.into()) {
+                return Ok(TxStatus::Reverted);
+            }
+            let block = receipt.block_number.map(|block| block.as_u64());
+            return Ok(TxStatus::Mined(Receipt { tx_hash: Some(tx_hash), block }));
+        }
+        if provider.get_transaction(tx_hash).await?.is_some() {
+            return Ok(TxStatus::Pending);
+        }
+        // Resending re-reads the account nonce, so the new transaction takes the dropped one's nonce
+        // (or it was already taken) and the two can't both be mined
+        self.tx_nonce = None;
+        Ok(TxStatus::Dropped)
+    }
+}
+
+// Turns a failed contract call into a readable message, decoding the contract's custom errors
+// from the revert data when there is any.
+pub fn describe_contract_error<M: Middleware>(error: &ContractError<M>) -> String {
+    match error.decode_contract_revert::<SwordCollectionErrors>() {
+        Some(SwordCollectionErrors::InvalidColor(e)) => format!("color {} is not a sword color", e.color),
+        Some(SwordCollectionErrors::Overflow(_)) => "sword count overflowed".to_string(),
+        Some(SwordCollectionErrors::AccessControlUnauthorizedAccount(e)) if e.needed_role == keccak256("MINTER_ROLE") => format!("{:?} is not allowed to mint swords", e.account),
+        Some(SwordCollectionErrors::AccessControlUnauthorizedAccount(e)) => format!("{:?} is missing role {:?}", e.account, H256::from(e.needed_role)),
+        Some(SwordCollectionErrors::InvalidArrayLength(e)) => format!("got {} colors but {} amounts", e.colors_length, e.amounts_length),
+        Some(SwordCollectionErrors::InvalidDropSignature(_)) => "drop claim signature was rejected".to_string(),
+        Some(SwordCollectionErrors::DropExpired(e)) => format!("drop claim expired at {} (block time {})", e.expiry, e.timestamp),
+        Some(SwordCollectionErrors::InvalidDropNonce(e)) => format!("drop claim used nonce {}, expected {}", e.provided, e.expected),
+        Some(SwordCollectionErrors::ColorNotDroppable(e)) => format!("color {} can't drop, only be crafted", e.color),
+        Some(SwordCollectionErrors::RevertString(reason)) => reason,
+        None => error.to_string(),
+    }
+}
@@ -0,0 +1,175 @@
+// Follows the contract's ERC-1155 transfer logs for the player's wallet, polling `eth_getLogs` block
+// range by block range, so swords minted, crafted away or transferred outside the game (e.g. from the
+// website) show up without a restart. If the chain reorganizes under the blocks it has read, it asks
+// for the balances to be read again instead of trying to undo logs.
+
+use ethers::contract::{parse_log, EthEvent};
+use ethers::prelude::{Filter, Http, Middleware, Provider};
+use ethers::types::{Address, TxHash, H256, U256};
+use eyre::Result;
+use std::{collections::BTreeMap, time::Duration};
+use tokio::sync::mpsc;
+
+use super::rpc::{SwordCollectionEvents, TransferBatchFilter, TransferSingleFilter};
+use super::ChainEvent;
+
+const MAX_BLOCK_RANGE: u64 = 1000; // Blocks per eth_getLogs call, which some nodes cap
+
+pub struct InventoryWatcher {
+    provider: Provider<Http>,
+    contract: Address,
+    player: Address,
+    interval: Duration,
+    // Number and hash of the last block read
+    last_block: (u64, H256),
+}
+
+impl InventoryWatcher {
+    // Starts reading right after `inventory_block`, the block the initial inventory was read at, so
+    // no transfer falls between the two
+    pub fn new(provider: Provider<Http>, contract: Address, player: Address, interval: Duration, inventory_block: (u64, H256)) -> Self {
+        InventoryWatcher { provider, contract, player, interval, last_block: inventory_block }
+    }
+
+    // Polls until the game stops listening
+    pub async fn run(mut self, events: mpsc::UnboundedSender<ChainEvent>) {
+        while !events.is_closed() {
+            match self.poll().await {
+                Ok(found) => {
+                    for event in found {
+                        if events.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Watching the contract failed, trying again: {}", e),
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    // Reads the blocks mined since the last poll, up to `MAX_BLOCK_RANGE` of them
+    async fn poll(&mut self) -> Result<Vec<ChainEvent>> {
+        let head = self.provider.get_block_number().await?.as_u64();
+        let (last, last_hash) = self.last_block;
+
+        // Any reorg of the blocks already read changes the hash of the last one
+        if self.block_hash(last).await? != last_hash {
+            self.last_block = (head, self.block_hash(head).await?);
+            return Ok(vec![ChainEvent::Reorged { block: last }]);
+        }
+        let Some((from, to)) = block_range(last, head) else {
+            return Ok(Vec::new());
+        };
+
+        let to_hash = self.block_hash(to).await?;
+        let filter = Filter::new()
+            .address(self.contract)
+            .from_block(from)
+            .to_block(to)
+            .topic0(vec![TransferSingleFilter::signature(), TransferBatchFilter::signature()]);
+        let logs = self.provider.get_logs(&filter).await?;
+
+        // Changes per transaction, so the game can tell its own transactions apart
+        let mut changes: BTreeMap<(u64, TxHash), Vec<(u8, i64)>> = BTreeMap::new();
+        for log in logs {
+            let (Some(block), Some(tx_hash)) = (log.block_number, log.transaction_hash) else {
+                continue;
+            };
+            let (from, to, transfers) = match parse_log::<SwordCollectionEvents>(log)? {
+                SwordCollectionEvents::TransferSingleFilter(e) => (e.from, e.to, vec![(e.id, e.value)]),
+                SwordCollectionEvents::TransferBatchFilter(e) => (e.from, e.to, e.ids.into_iter().zip(e.values).collect()),
+            };
+            for (id, value) in transfers {
+                let Some(change) = self.change(from, to, id, value) else {
+                    continue;
+                };
+                changes.entry((block.as_u64(), tx_hash)).or_default().push(change);
+            }
+        }
+
+        // A reorg while reading shows up as a changed hash for `to` next poll
+        self.last_block = (to, to_hash);
+        Ok(changes.into_iter()
+            .map(|((block, tx_hash), changes)| ChainEvent::InventoryChanged { tx_hash, block, changes })
+            .collect())
+    }
+
+    // How a transfer of `value` swords of color `id` changes the player's count, if it does. Ids that
+    // don't fit a color id are skipped rather than wrapped onto another color.
+    fn change(&self, from: Address, to: Address, id: U256, value: U256) -> Option<(u8, i64)> {
+        let color = u8::try_from(id).ok()?;
+        if from == to {
+            return None;
+        }
+        let value = value.min(U256::from(i64::MAX)).as_u64() as i64;
+        if to == self.player {
+            Some((color, value))
+        } else if from == self.player {
+            Some((color, -value))
+        } else {
+            None
+        }
+    }
+
+    async fn block_hash(&self, number: u64) -> Result<H256> {
+        self.provider
+            .get_block(number)
+            .await?
+            .and_then(|block| block.hash)
+            .ok_or_else(|| eyre::eyre!("block {} not found", number))
+    }
+}
+
+// The blocks to read next, inclusive, after `last` was read and with `head` the newest block
+fn block_range(last: u64, head: u64) -> Option<(u64, u64)> {
+    (head > last).then(|| (last + 1, head.min(last + MAX_BLOCK_RANGE)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYER: Address = Address::repeat_byte(1);
+    const OTHER: Address = Address::repeat_byte(2);
+
+    fn watcher() -> InventoryWatcher {
+        let provider = Provider::<Http>::try_from("http://localhost:8547").unwrap();
+        InventoryWatcher::new(provider, Address::zero(), PLAYER, Duration::from_secs(1), (0, H256::zero()))
+    }
+
+    #[test]
+    fn reading_starts_right_after_the_last_block() {
+        // Blocks mined after the inventory was read at block 10 are all read
+        assert_eq!(block_range(10, 12), Some((11, 12)));
+        assert_eq!(block_range(10, 11), Some((11, 11)));
+        assert_eq!(block_range(10, 10), None);
+        assert_eq!(block_range(10, 9), None);
+        assert_eq!(block_range(10, 10 + MAX_BLOCK_RANGE + 5), Some((11, 10 + MAX_BLOCK_RANGE)));
+    }
+
+    #[test]
+    fn transfers_change_the_players_count() {
+        let watcher = watcher();
+        assert_eq!(watcher.change(Address::zero(), PLAYER, U256::from(2), U256::from(3)), Some((2, 3)));
+        assert_eq!(watcher.change(PLAYER, OTHER, U256::from(0), U256::from(1)), Some((0, -1)));
+        assert_eq!(watcher.change(OTHER, Address::zero(), U256::from(0), U256::from(1)), None);
+        assert_eq!(watcher.change(PLAYER, PLAYER, U256::from(0), U256::from(1)), None);
+    }
+
+    #[test]
+    fn ids_past_u8_are_skipped_instead_of_wrapped() {
+        let watcher = watcher();
+        assert_eq!(watcher.change(Address::zero(), PLAYER, U256::from(255), U256::from(1)), Some((255, 1)));
+        // 256 and 258 would alias colors 0 and 2 if truncated
+        assert_eq!(watcher.change(Address::zero(), PLAYER, U256::from(256), U256::from(1)), None);
+        assert_eq!(watcher.change(Address::zero(), PLAYER, U256::from(258), U256::from(1)), None);
+        assert_eq!(watcher.change(Address::zero(), PLAYER, U256::MAX, U256::from(1)), None);
+    }
+
+    #[test]
+    fn huge_values_saturate() {
+        let watcher = watcher();
+        assert_eq!(watcher.change(Address::zero(), PLAYER, U256::from(1), U256::MAX), Some((1, i64::MAX)));
+    }
+}
@@ -0,0 +1,441 @@
+// Enemy archetypes, read from `assets/enemies.json`. Each kind has its own sprites, health, speed, drop
+// table and a list of behaviors, and every behavior is its own component with its own system, so an
+// archetype is just the behaviors it is made of: a charger chases and dashes, a shooter keeps its
+// distance and shoots. Kinds the file doesn't list use its `default` entry.
+
+use bevy::prelude::*;
+use eyre::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::animation::{Animator, SpriteSheets};
+use crate::health::{hurt_player, Health, Vulnerable};
+use crate::states::InGame;
+use crate::weapons::StatusEffect;
+use crate::{Enemy, GameState, Knockback};
+
+pub const ENEMIES_PATH: &str = "assets/enemies.json";
+const PROJECTILE_HIT_RANGE: f32 = 30.0;
+const PROJECTILE_LIFETIME_SECS: f32 = 4.0;
+const TELEGRAPH_FLASH_RATE: f32 = 12.0; // Blinks per second while a dash winds up
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnemyKind {
+    Chaser,
+    Shooter,
+    Charger,
+    Splitter,
+    // What a splitter splits into
+    Splitling,
+}
+
+impl EnemyKind {
+    pub const ALL: [EnemyKind; 5] = [
+        EnemyKind::Chaser,
+        EnemyKind::Shooter,
+        EnemyKind::Charger,
+        EnemyKind::Splitter,
+        EnemyKind::Splitling,
+    ];
+}
+
+#[derive(Clone, Deserialize)]
+pub struct EnemyDef {
+    // Variant of the `enemy` sheet in `assets/animations.json`, so `sheets/enemy/<sprite>.png`
+    pub sprite: String,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    pub health: f32,
+    // Walking speed, before a wave's speed multiplier
+    pub speed: f32,
+    pub behaviors: Vec<Behavior>,
+    #[serde(default)]
+    pub drops: DropTable,
+}
+
+fn default_scale() -> f32 {
+    4.0
+}
+
+impl Default for EnemyDef {
+    // The enemy the game had before archetypes
+    fn default() -> Self {
+        EnemyDef {
+            sprite: "chaser".to_string(),
+            scale: default_scale(),
+            health: 3.0,
+            speed: 100.0,
+            behaviors: vec![Behavior::Chase],
+            drops: DropTable::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Behavior {
+    // Walks straight at the player
+    Chase,
+    // Walks up to `max` from the player and backs off when closer than `min`
+    KeepDistance { min: f32, max: f32 },
+    // Fires a projectile at the player every `interval` seconds while within `range`
+    Shoot { interval: f32, range: f32, projectile_speed: f32, damage: f32 },
+    // Stops and flashes for `windup_secs` once within `range`, then dashes where the player was
+    Dash { range: f32, windup_secs: f32, dash_secs: f32, dash_speed: f32, cooldown_secs: f32 },
+    // Spawns `count` enemies of kind `into` on death
+    Split { into: EnemyKind, count: u32 },
+}
+
+#[derive(Clone, Deserialize)]
+pub struct DropTable {
+    // Chance of dropping anything at all
+    #[serde(default = "default_drop_chance")]
+    pub chance: f32,
+    #[serde(default = "default_drop_count")]
+    pub count: u32,
+    // Weights by color name. Empty uses the registry's weights; otherwise only these colors drop.
+    #[serde(default)]
+    pub colors: HashMap<String, u32>,
+}
+
+fn default_drop_chance() -> f32 {
+    1.0
+}
+
+fn default_drop_count() -> u32 {
+    1
+}
+
+impl Default for DropTable {
+    // One sword, weighted like the registry says
+    fn default() -> Self {
+        DropTable { chance: default_drop_chance(), count: default_drop_count(), colors: HashMap::new() }
+    }
+}
+
+#[derive(Resource, Default, Deserialize)]
+pub struct EnemyTable {
+    #[serde(default)]
+    default: EnemyDef,
+    #[serde(default)]
+    kinds: HashMap<EnemyKind, EnemyDef>,
+}
+
+impl EnemyTable {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn def(&self, kind: EnemyKind) -> &EnemyDef {
+        self.kinds.get(&kind).unwrap_or(&self.default)
+    }
+}
+
+pub fn load_enemy_table(mut commands: Commands) {
+    let table = EnemyTable::load(ENEMIES_PATH).unwrap_or_else(|e| {
+        eprintln!("Couldn't load {}, every enemy is a chaser: {}", ENEMIES_PATH, e);
+        EnemyTable::default()
+    });
+    commands.insert_resource(table);
+}
+
+// Asks for an enemy to be spawned at the end of the frame's gameplay
+#[derive(Event)]
+pub struct SpawnEnemy {
+    pub kind: EnemyKind,
+    pub position: Vec3,
+    pub speed_multiplier: f32,
+}
+
+// How fast an enemy walks
+#[derive(Component)]
+pub struct Speed(pub f32);
+
+// What an enemy drops when it dies
+#[derive(Component)]
+pub struct Loot(pub DropTable);
+
+#[derive(Component)]
+pub struct Chase;
+
+// Chasers whose movement no other behavior has taken over
+type Chasing = (With<Chase>, Without<MovementLocked>);
+
+#[derive(Component)]
+pub struct KeepDistance {
+    min: f32,
+    max: f32,
+}
+
+#[derive(Component)]
+pub struct Shoot {
+    timer: Timer,
+    range: f32,
+    projectile_speed: f32,
+    damage: f32,
+}
+
+#[derive(Component)]
+pub struct Dash {
+    range: f32,
+    windup_secs: f32,
+    dash_secs: f32,
+    dash_speed: f32,
+    cooldown_secs: f32,
+    phase: DashPhase,
+}
+
+enum DashPhase {
+    Ready,
+    Windup(Timer),
+    Dashing { timer: Timer, direction: Vec3 },
+    Cooldown(Timer),
+}
+
+#[derive(Component)]
+pub struct SplitOnDeath {
+    pub into: EnemyKind,
+    pub count: u32,
+}
+
+// Set while a behavior has taken over an enemy's movement, so the walking behaviors leave it alone
+#[derive(Component)]
+pub struct MovementLocked;
+
+#[derive(Component)]
+pub struct Projectile {
+    velocity: Vec3,
+    damage: f32,
+    lifetime: Timer,
+}
+
+// A point just outside the screen, on a random edge
+pub fn random_edge_position() -> Vec3 {
+    // Screen boundaries (assuming 800x600, but we'll use larger values for safety)
+    let screen_width = 1000.0;
+    let screen_height = 800.0;
+
+    let (x, y) = match rand::random::<u8>() % 4 {
+        0 => (rand::random::<f32>() * screen_width - screen_width / 2.0, screen_height / 2.0 + 50.0), // Top edge
+        1 => (rand::random::<f32>() * screen_width - screen_width / 2.0, -screen_height / 2.0 - 50.0), // Bottom edge
+        2 => (-screen_width / 2.0 - 50.0, rand::random::<f32>() * screen_height - screen_height / 2.0), // Left edge
+        _ => (screen_width / 2.0 + 50.0, rand::random::<f32>() * screen_height - screen_height / 2.0), // Right edge
+    };
+    Vec3::new(x, y, 0.0)
+}
+
+pub fn spawn_enemies(
+    mut commands: Commands,
+    mut requests: EventReader<SpawnEnemy>,
+    table: Res<EnemyTable>,
+    sheets: Res<SpriteSheets>,
+) {
+    for request in requests.read() {
+        let def = table.def(request.kind);
+        // Check if assets are loaded
+        let Some((texture, layout)) = sheets.get("enemy", &def.sprite) else {
+            continue;
+        };
+        let mut enemy = commands.spawn((
+            SpriteBundle {
+                texture: texture.clone(),
+                transform: Transform::from_translation(request.position).with_scale(Vec3::splat(def.scale)),
+                ..default()
+            },
+            Enemy,
+            request.kind,
+            Speed(def.speed * request.speed_multiplier),
+            Health::new(def.health),
+            Knockback(Vec3::ZERO),
+            Loot(def.drops.clone()),
+            StateScoped(InGame),
+            TextureAtlas::from(layout.clone()),
+            Animator::new("enemy", &def.sprite, "walk"),
+        ));
+        for behavior in &def.behaviors {
+            match *behavior {
+                Behavior::Chase => {
+                    enemy.insert(Chase);
+                }
+                Behavior::KeepDistance { min, max } => {
+                    enemy.insert(KeepDistance { min, max });
+                }
+                Behavior::Shoot { interval, range, projectile_speed, damage } => {
+                    let timer = Timer::from_seconds(interval, TimerMode::Repeating);
+                    enemy.insert(Shoot { timer, range, projectile_speed, damage });
+                }
+                Behavior::Dash { range, windup_secs, dash_secs, dash_speed, cooldown_secs } => {
+                    enemy.insert(Dash { range, windup_secs, dash_secs, dash_speed, cooldown_secs, phase: DashPhase::Ready });
+                }
+                Behavior::Split { into, count } => {
+                    enemy.insert(SplitOnDeath { into, count });
+                }
+            }
+        }
+    }
+}
+
+// How fast the enemy walks right now, after slowing effects
+fn walk_speed(speed: &Speed, status: Option<&StatusEffect>) -> f32 {
+    speed.0 * status.map_or(1.0, |status| status.speed_factor())
+}
+
+pub fn chase(
+    mut query: Query<(&mut Transform, &Speed, Option<&StatusEffect>), Chasing>,
+    game_state: Res<GameState>,
+    time: Res<Time>,
+) {
+    for (mut transform, speed, status) in query.iter_mut() {
+        let direction = (game_state.player_position - transform.translation).normalize_or_zero();
+        transform.translation += direction * walk_speed(speed, status) * time.delta_seconds();
+    }
+}
+
+pub fn keep_distance(
+    mut query: Query<(&mut Transform, &Speed, &KeepDistance, Option<&StatusEffect>), Without<MovementLocked>>,
+    game_state: Res<GameState>,
+    time: Res<Time>,
+) {
+    for (mut transform, speed, keep_distance, status) in query.iter_mut() {
+        let to_player = game_state.player_position - transform.translation;
+        let distance = to_player.length();
+        let direction = if distance > keep_distance.max {
+            to_player.normalize_or_zero()
+        } else if distance < keep_distance.min {
+            -to_player.normalize_or_zero()
+        } else {
+            continue;
+        };
+        transform.translation += direction * walk_speed(speed, status) * time.delta_seconds();
+    }
+}
+
+pub fn shoot(
+    mut commands: Commands,
+    mut query: Query<(&Transform, &mut Shoot)>,
+    game_state: Res<GameState>,
+    time: Res<Time>,
+) {
+    for (transform, mut shoot) in query.iter_mut() {
+        if !shoot.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        let to_player = game_state.player_position - transform.translation;
+        if to_player.length() > shoot.range {
+            continue;
+        }
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::srgb(1.0, 0.5, 0.9),
+                    custom_size: Some(Vec2::splat(12.0)),
+                    ..default()
+                },
+                transform: Transform::from_translation(transform.translation.truncate().extend(1.0)),
+                ..default()
+            },
+            Projectile {
+                velocity: to_player.normalize_or_zero() * shoot.projectile_speed,
+                damage: shoot.damage,
+                lifetime: Timer::from_seconds(PROJECTILE_LIFETIME_SECS, TimerMode::Once),
+            },
+            StateScoped(InGame),
+        ));
+    }
+}
+
+// Ready -> winding up (standing still and flashing) -> dashing -> cooling down -> ready
+pub fn dash(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Transform, &mut Dash, &mut Sprite)>,
+    game_state: Res<GameState>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, mut dash, mut sprite) in query.iter_mut() {
+        let dash = &mut *dash;
+        match &mut dash.phase {
+            DashPhase::Ready => {
+                if transform.translation.distance(game_state.player_position) < dash.range {
+                    dash.phase = DashPhase::Windup(Timer::from_seconds(dash.windup_secs, TimerMode::Once));
+                    commands.entity(entity).insert(MovementLocked);
+                }
+            }
+            DashPhase::Windup(timer) => {
+                if timer.tick(time.delta()).finished() {
+                    sprite.color = Color::WHITE;
+                    let direction = (game_state.player_position - transform.translation).normalize_or_zero();
+                    let timer = Timer::from_seconds(dash.dash_secs, TimerMode::Once);
+                    dash.phase = DashPhase::Dashing { timer, direction };
+                } else {
+                    let lit = ((timer.elapsed_secs() * TELEGRAPH_FLASH_RATE) as u32).is_multiple_of(2);
+                    sprite.color = if lit { Color::srgb(1.0, 0.3, 0.3) } else { Color::WHITE };
+                }
+            }
+            DashPhase::Dashing { timer, direction } => {
+                transform.translation += *direction * dash.dash_speed * time.delta_seconds();
+                if timer.tick(time.delta()).finished() {
+                    dash.phase = DashPhase::Cooldown(Timer::from_seconds(dash.cooldown_secs, TimerMode::Once));
+                    commands.entity(entity).remove::<MovementLocked>();
+                }
+            }
+            DashPhase::Cooldown(timer) => {
+                if timer.tick(time.delta()).finished() {
+                    dash.phase = DashPhase::Ready;
+                }
+            }
+        }
+    }
+}
+
+// Splits enemies about to be despawned by `enemy_deaths`
+pub fn split_on_death(
+    query: Query<(&Transform, &Health, &SplitOnDeath)>,
+    mut spawns: EventWriter<SpawnEnemy>,
+) {
+    for (transform, health, split) in query.iter() {
+        if !health.is_dead() {
+            continue;
+        }
+        for _ in 0..split.count {
+            let offset = Vec2::from_angle(rand::random::<f32>() * std::f32::consts::TAU) * 30.0;
+            spawns.send(SpawnEnemy {
+                kind: split.into,
+                position: transform.translation + offset.extend(0.0),
+                speed_multiplier: 1.0,
+            });
+        }
+    }
+}
+
+pub fn move_projectiles(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Transform, &mut Projectile)>,
+    time: Res<Time>,
+) {
+    for (entity, mut transform, mut projectile) in query.iter_mut() {
+        transform.translation += projectile.velocity * time.delta_seconds();
+        if projectile.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn projectile_hits(
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &Transform, &mut Health), Vulnerable>,
+    projectile_query: Query<(Entity, &Transform, &Projectile)>,
+) {
+    let Ok((player, player_transform, mut health)) = player_query.get_single_mut() else {
+        return;
+    };
+    for (entity, transform, projectile) in projectile_query.iter() {
+        if transform.translation.truncate().distance(player_transform.translation.truncate()) < PROJECTILE_HIT_RANGE {
+            commands.entity(entity).despawn();
+            hurt_player(&mut commands, player, &mut health, projectile.damage);
+            // Invulnerable until next frame's query sees it
+            return;
+        }
+    }
+}
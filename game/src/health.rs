@@ -0,0 +1,168 @@
+// Player health. Enemies hurt the player on contact and with their projectiles, each hit is
+// followed by a moment of invulnerability, and the run ends at zero HP. Dying throws away the swords
+// that haven't been sent to the backend yet: only what made it on-chain is safe.
+
+use bevy::prelude::*;
+
+use crate::states::{AppState, InGame, RunStats};
+use crate::spatial::SpatialHash;
+use crate::{Enemy, GameState, Player, SwordBuffer};
+
+pub const PLAYER_MAX_HEALTH: f32 = 5.0;
+const ENEMY_CONTACT_DAMAGE: f32 = 1.0;
+const ENEMY_CONTACT_RANGE: f32 = 50.0; // Sized for 4x sprites
+const INVULNERABILITY_SECS: f32 = 1.0;
+const FLASH_RATE: f32 = 10.0; // Blinks per second while invulnerable
+const HEALTH_BAR_WIDTH: f32 = 200.0;
+
+#[derive(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Health { current: max, max }
+    }
+
+    pub fn damage(&mut self, amount: f32) {
+        self.current = (self.current - amount).max(0.0);
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+// Ignores contact damage until the timer runs out
+#[derive(Component)]
+pub struct Invulnerable(Timer);
+
+// The player, while it can take a hit
+pub type Vulnerable = (With<Player>, Without<Invulnerable>);
+
+#[derive(Component)]
+pub struct HealthBarFill;
+
+#[derive(Component)]
+pub struct HealthText;
+
+pub fn spawn_health_bar(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(8.0),
+                    ..default()
+                },
+                ..default()
+            },
+            StateScoped(InGame),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 20.0, color: Color::WHITE, ..default() }),
+                HealthText,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style { width: Val::Px(HEALTH_BAR_WIDTH), height: Val::Px(16.0), ..default() },
+                    background_color: Color::srgb(0.25, 0.05, 0.05).into(),
+                    ..default()
+                })
+                .with_children(|bar| {
+                    bar.spawn((
+                        NodeBundle {
+                            style: Style { width: Val::Percent(100.0), height: Val::Percent(100.0), ..default() },
+                            background_color: Color::srgb(0.85, 0.1, 0.1).into(),
+                            ..default()
+                        },
+                        HealthBarFill,
+                    ));
+                });
+        });
+}
+
+pub fn enemy_contact_damage(
+    mut commands: Commands,
+    mut player_query: Query<(Entity, &Transform, &mut Health), Vulnerable>,
+    enemy_query: Query<&Transform, With<Enemy>>,
+    enemy_hash: Res<SpatialHash<Enemy>>,
+) {
+    let Ok((player, player_transform, mut health)) = player_query.get_single_mut() else {
+        return;
+    };
+    let player_position = player_transform.translation.truncate();
+    let touched = enemy_hash.query_circle(player_position, ENEMY_CONTACT_RANGE)
+        .filter_map(|candidate| enemy_query.get(candidate).ok())
+        .any(|enemy| enemy.translation.truncate().distance(player_position) < ENEMY_CONTACT_RANGE);
+    if touched {
+        hurt_player(&mut commands, player, &mut health, ENEMY_CONTACT_DAMAGE);
+    }
+}
+
+// Takes `amount` off the player's health, followed by a moment of invulnerability if they survive
+pub fn hurt_player(commands: &mut Commands, player: Entity, health: &mut Health, amount: f32) {
+    health.damage(amount);
+    if !health.is_dead() {
+        commands.entity(player).insert(Invulnerable(Timer::from_seconds(INVULNERABILITY_SECS, TimerMode::Once)));
+    }
+}
+
+// Ends the run once the player runs out of health
+pub fn player_death(
+    player_query: Query<&Health, (With<Player>, Changed<Health>)>,
+    mut sword_buffer: ResMut<SwordBuffer>,
+    mut game_state: ResMut<GameState>,
+    mut run_stats: ResMut<RunStats>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !player_query.get_single().is_ok_and(|health| health.is_dead()) {
+        return;
+    }
+
+    // The swords still waiting in the buffer die with the player
+    let lost = sword_buffer.take();
+    game_state.remove_pending(&lost);
+    run_stats.swords_lost = lost.iter().map(|&(_, amount)| amount).sum();
+    run_stats.died = true;
+    next_state.set(AppState::GameOver);
+}
+
+// Blinks the player while invulnerable and ends it when the timer runs out
+pub fn tick_invulnerability(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut player_query: Query<(Entity, &mut Invulnerable, &mut Sprite), With<Player>>,
+) {
+    for (entity, mut invulnerable, mut sprite) in player_query.iter_mut() {
+        if invulnerable.0.tick(time.delta()).finished() {
+            sprite.color.set_alpha(1.0);
+            commands.entity(entity).remove::<Invulnerable>();
+        } else {
+            let visible = ((invulnerable.0.elapsed_secs() * FLASH_RATE) as u32).is_multiple_of(2);
+            sprite.color.set_alpha(if visible { 1.0 } else { 0.3 });
+        }
+    }
+}
+
+pub fn update_health_bar(
+    player_query: Query<&Health, (With<Player>, Changed<Health>)>,
+    mut fill_query: Query<&mut Style, With<HealthBarFill>>,
+    mut text_query: Query<&mut Text, With<HealthText>>,
+) {
+    let Ok(health) = player_query.get_single() else {
+        return;
+    };
+    for mut style in fill_query.iter_mut() {
+        style.width = Val::Percent(100.0 * health.current / health.max.max(1.0));
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = format!("HP {}/{}", health.current.ceil(), health.max);
+    }
+}
@@ -0,0 +1,368 @@
+// Sword hitboxes and enemy hurtboxes. Each frame of the 4-frame swing hits with its own shapes per
+// direction, read from `assets/hitboxes.json`: arc sectors around the player and oriented
+// rectangles, scaled by the sword's reach. Enemies get a box around the opaque pixels of their
+// sprite. F3 draws all of it.
+
+use bevy::prelude::*;
+use bevy::render::render_resource::TextureFormat;
+use eyre::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::{Enemy, GameState, PlayerDirection};
+use crate::weapons::WeaponTable;
+
+pub const HITBOXES_PATH: &str = "assets/hitboxes.json";
+const ARC_SEGMENT: f32 = 15.0; // Degrees of arc per polygon edge
+const SWING_FRAMES: usize = 4;
+
+// Relative to the player, in world units for a sword of `base_reach`. Angles are in degrees,
+// counterclockwise from the right.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum HitShape {
+    // Sector around the player, swept from `from` to `to`
+    Arc { radius: f32, from: f32, to: f32 },
+    // Rectangle of `size` centered at `center`, rotated by `angle`
+    Rect { center: [f32; 2], size: [f32; 2], angle: f32 },
+}
+
+impl HitShape {
+    // The shape as convex polygons around `origin`, scaled by `scale`
+    fn polygons(&self, origin: Vec2, scale: f32) -> Vec<Vec<Vec2>> {
+        match *self {
+            HitShape::Arc { radius, from, to } => {
+                let (start, end) = (from.min(to), from.max(to));
+                // Pieces of at most 90 degrees, so each one is convex
+                let pieces = ((end - start) / 90.0).ceil().max(1.0) as usize;
+                let piece = (end - start) / pieces as f32;
+                (0..pieces)
+                    .map(|i| {
+                        let piece_start = start + piece * i as f32;
+                        let segments = (piece / ARC_SEGMENT).ceil().max(1.0) as usize;
+                        let mut polygon = vec![origin];
+                        polygon.extend((0..=segments).map(|s| {
+                            let angle = (piece_start + piece * s as f32 / segments as f32).to_radians();
+                            origin + Vec2::from_angle(angle) * radius * scale
+                        }));
+                        polygon
+                    })
+                    .collect()
+            }
+            HitShape::Rect { center, size, angle } => {
+                let rotation = Vec2::from_angle(angle.to_radians());
+                let center = origin + Vec2::from(center) * scale;
+                let half = Vec2::from(size) * scale / 2.0;
+                let corners = [Vec2::new(-half.x, -half.y), Vec2::new(half.x, -half.y), half, Vec2::new(-half.x, half.y)];
+                vec![corners.iter().map(|&corner| center + rotation.rotate(corner)).collect()]
+            }
+        }
+    }
+}
+
+// The shapes of each swing frame, per direction
+#[derive(Deserialize)]
+pub struct SwingDirections {
+    up: Vec<Vec<HitShape>>,
+    down: Vec<Vec<HitShape>>,
+    left: Vec<Vec<HitShape>>,
+    right: Vec<Vec<HitShape>>,
+}
+
+#[derive(Resource, Deserialize)]
+pub struct SwingHitboxes {
+    // Reach of the sword the shapes are sized for
+    base_reach: f32,
+    swing: SwingDirections,
+}
+
+impl Default for SwingHitboxes {
+    // A sweep in front of the player on the middle two frames, turning the way the sprites do
+    fn default() -> Self {
+        let sweep = |first: (f32, f32), second: (f32, f32)| {
+            vec![
+                vec![],
+                vec![HitShape::Arc { radius: 100.0, from: first.0, to: first.1 }],
+                vec![HitShape::Arc { radius: 95.0, from: second.0, to: second.1 }],
+                vec![],
+            ]
+        };
+        SwingHitboxes {
+            base_reach: 50.0,
+            swing: SwingDirections {
+                up: sweep((30.0, 120.0), (90.0, 165.0)),
+                down: sweep((240.0, 330.0), (195.0, 270.0)),
+                left: sweep((150.0, 240.0), (105.0, 180.0)),
+                right: sweep((-60.0, 30.0), (0.0, 75.0)),
+            },
+        }
+    }
+}
+
+impl SwingHitboxes {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let hitboxes: SwingHitboxes = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let swing = &hitboxes.swing;
+        for frames in [&swing.up, &swing.down, &swing.left, &swing.right] {
+            if frames.len() != SWING_FRAMES {
+                eyre::bail!("every direction needs {} swing frames, got {}", SWING_FRAMES, frames.len());
+            }
+        }
+        Ok(hitboxes)
+    }
+
+    // What frame `frame` of a swing towards `direction` covers, as convex polygons in the world
+    pub fn swing_polygons(&self, direction: PlayerDirection, frame: u8, origin: Vec2, reach: f32) -> Vec<Vec<Vec2>> {
+        let frames = match direction {
+            PlayerDirection::Up => &self.swing.up,
+            PlayerDirection::Down => &self.swing.down,
+            PlayerDirection::Left => &self.swing.left,
+            PlayerDirection::Right => &self.swing.right,
+        };
+        let scale = reach / self.base_reach;
+        frames.get(frame as usize).into_iter().flatten()
+            .flat_map(|shape| shape.polygons(origin, scale))
+            .collect()
+    }
+}
+
+pub fn load_swing_hitboxes(mut commands: Commands) {
+    let hitboxes = SwingHitboxes::load(HITBOXES_PATH).unwrap_or_else(|e| {
+        eprintln!("Couldn't load {}, using the default swing hitboxes: {}", HITBOXES_PATH, e);
+        SwingHitboxes::default()
+    });
+    commands.insert_resource(hitboxes);
+}
+
+// Where an enemy can be hit, relative to its position
+#[derive(Component)]
+pub struct Hurtbox(Rect);
+
+impl Hurtbox {
+    pub fn world_rect(&self, transform: &Transform) -> Rect {
+        let position = transform.translation.truncate();
+        Rect::from_corners(self.0.min + position, self.0.max + position)
+    }
+}
+
+// Enemies still waiting for their sprite to load
+type Unsized = (With<Enemy>, Without<Hurtbox>);
+
+// A sheet and the index of one of its frames
+type SheetFrame = (AssetId<Image>, usize);
+
+// Gives enemies a hurtbox once their sprite has loaded
+pub fn size_hurtboxes(
+    mut commands: Commands,
+    query: Query<(Entity, &Handle<Image>, &TextureAtlas, &Transform), Unsized>,
+    images: Res<Assets<Image>>,
+    layouts: Res<Assets<TextureAtlasLayout>>,
+    // Opaque bounds of each frame of each sheet, in pixels from its center
+    mut bounds: Local<HashMap<SheetFrame, Rect>>,
+) {
+    for (entity, texture, atlas, transform) in query.iter() {
+        let key = (texture.id(), atlas.index);
+        let rect = match bounds.get(&key) {
+            Some(rect) => *rect,
+            None => {
+                let (Some(image), Some(frame)) = (images.get(texture), atlas.texture_rect(&layouts)) else {
+                    continue;
+                };
+                let rect = opaque_bounds(image, frame);
+                bounds.insert(key, rect);
+                rect
+            }
+        };
+        let scale = transform.scale.truncate();
+        commands.entity(entity).insert(Hurtbox(Rect::from_corners(rect.min * scale, rect.max * scale)));
+    }
+}
+
+// The box around the pixels of the `frame` that aren't fully transparent, or the whole frame if it can't tell
+fn opaque_bounds(image: &Image, frame: URect) -> Rect {
+    let size = frame.size().as_vec2();
+    let whole = Rect::from_center_size(Vec2::ZERO, size);
+    if !matches!(image.texture_descriptor.format, TextureFormat::Rgba8UnormSrgb | TextureFormat::Rgba8Unorm) {
+        return whole;
+    }
+    let width = image.width() as usize;
+    let mut opaque: Option<Rect> = None;
+    for image_y in frame.min.y..frame.max.y {
+        for image_x in frame.min.x..frame.max.x {
+            let alpha = (image_y as usize * width + image_x as usize) * 4 + 3;
+            if image.data.get(alpha).is_none_or(|&alpha| alpha == 0) {
+                continue;
+            }
+            // Image rows go down, the world's y goes up
+            let (x, y) = ((image_x - frame.min.x) as f32, (image_y - frame.min.y) as f32);
+            let pixel = Rect::new(x - size.x / 2.0, size.y / 2.0 - y - 1.0, x + 1.0 - size.x / 2.0, size.y / 2.0 - y);
+            opaque = Some(opaque.map_or(pixel, |opaque| opaque.union(pixel)));
+        }
+    }
+    opaque.unwrap_or(whole)
+}
+
+// Separating axis test between a convex polygon and an axis-aligned box
+pub fn overlaps(polygon: &[Vec2], rect: Rect) -> bool {
+    let corners = [rect.min, Vec2::new(rect.max.x, rect.min.y), rect.max, Vec2::new(rect.min.x, rect.max.y)];
+    let edge_normals = polygon.iter().zip(polygon.iter().cycle().skip(1)).map(|(a, b)| (*b - *a).perp());
+    [Vec2::X, Vec2::Y].into_iter().chain(edge_normals).all(|axis| {
+        let (polygon_min, polygon_max) = project(polygon, axis);
+        let (rect_min, rect_max) = project(&corners, axis);
+        polygon_min <= rect_max && rect_min <= polygon_max
+    })
+}
+
+fn project(points: &[Vec2], axis: Vec2) -> (f32, f32) {
+    points.iter().map(|point| point.dot(axis)).fold((f32::MAX, f32::MIN), |(min, max), d| (min.min(d), max.max(d)))
+}
+
+// Whether hitboxes are drawn, toggled with F3 or turned on from the start with DEBUG_HITBOXES=true
+#[derive(Resource)]
+pub struct HitboxDebug(pub bool);
+
+impl HitboxDebug {
+    pub fn from_env() -> Self {
+        HitboxDebug(std::env::var("DEBUG_HITBOXES").is_ok_and(|debug| debug == "true"))
+    }
+}
+
+pub fn toggle_hitbox_debug(keyboard: Res<ButtonInput<KeyCode>>, mut debug: ResMut<HitboxDebug>) {
+    if keyboard.just_pressed(KeyCode::F3) {
+        debug.0 = !debug.0;
+    }
+}
+
+pub fn draw_hitboxes(
+    mut gizmos: Gizmos,
+    game_state: Res<GameState>,
+    hitboxes: Res<SwingHitboxes>,
+    weapons: Res<WeaponTable>,
+    hurtboxes: Query<(&Hurtbox, &Transform)>,
+) {
+    for (hurtbox, transform) in hurtboxes.iter() {
+        let rect = hurtbox.world_rect(transform);
+        gizmos.rect_2d(rect.center(), 0.0, rect.size(), Color::srgb(0.2, 0.6, 1.0));
+    }
+
+    if !game_state.is_swinging {
+        return;
+    }
+    let origin = game_state.player_position.truncate();
+    let reach = game_state.equipped_weapon(&weapons).reach;
+    for polygon in hitboxes.swing_polygons(game_state.player_direction, game_state.swing_frame, origin, reach) {
+        gizmos.linestrip_2d(polygon.iter().chain(polygon.first()).copied(), Color::srgb(1.0, 0.3, 0.2));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::render_asset::RenderAssetUsages;
+    use bevy::render::render_resource::{Extent3d, TextureDimension};
+
+    fn square(min: Vec2, size: f32) -> Vec<Vec2> {
+        vec![min, min + Vec2::new(size, 0.0), min + Vec2::splat(size), min + Vec2::new(0.0, size)]
+    }
+
+    // A `width` x `height` image, transparent except for the `opaque` pixels
+    fn image(width: u32, height: u32, opaque: &[(u32, u32)]) -> Image {
+        let mut data = vec![0; (width * height * 4) as usize];
+        for &(x, y) in opaque {
+            data[((y * width + x) * 4 + 3) as usize] = 255;
+        }
+        let size = Extent3d { width, height, depth_or_array_layers: 1 };
+        Image::new(size, TextureDimension::D2, data, TextureFormat::Rgba8UnormSrgb, RenderAssetUsages::default())
+    }
+
+    fn centroid(polygons: &[Vec<Vec2>]) -> Vec2 {
+        let points: Vec<Vec2> = polygons.iter().flatten().copied().collect();
+        points.iter().sum::<Vec2>() / points.len() as f32
+    }
+
+    #[test]
+    fn separated_shapes_dont_overlap() {
+        let rect = Rect::new(0.0, 0.0, 10.0, 10.0);
+        assert!(!overlaps(&square(Vec2::new(11.0, 0.0), 5.0), rect));
+        assert!(!overlaps(&square(Vec2::new(0.0, -6.0), 5.0), rect));
+        // A diamond whose bounding box overlaps the rect, but only the edge normals separate them
+        let diamond = [Vec2::new(12.0, 16.0), Vec2::new(16.0, 12.0), Vec2::new(20.0, 16.0), Vec2::new(16.0, 20.0)];
+        assert!(!overlaps(&diamond, Rect::new(0.0, 0.0, 13.0, 13.0)));
+    }
+
+    #[test]
+    fn touching_shapes_overlap() {
+        let rect = Rect::new(0.0, 0.0, 10.0, 10.0);
+        assert!(overlaps(&square(Vec2::new(10.0, 0.0), 5.0), rect));
+        assert!(overlaps(&square(Vec2::new(10.0, 10.0), 5.0), rect));
+    }
+
+    #[test]
+    fn overlapping_shapes_overlap() {
+        let rect = Rect::new(0.0, 0.0, 10.0, 10.0);
+        assert!(overlaps(&square(Vec2::new(5.0, 5.0), 10.0), rect));
+        // Fully inside, and fully around
+        assert!(overlaps(&square(Vec2::new(2.0, 2.0), 2.0), rect));
+        assert!(overlaps(&square(Vec2::new(-5.0, -5.0), 20.0), rect));
+    }
+
+    #[test]
+    fn default_swings_sweep_in_front_of_the_player() {
+        let hitboxes = SwingHitboxes::default();
+        let origin = Vec2::new(100.0, -40.0);
+        for (direction, front) in [
+            (PlayerDirection::Up, Vec2::Y),
+            (PlayerDirection::Down, Vec2::NEG_Y),
+            (PlayerDirection::Left, Vec2::NEG_X),
+            (PlayerDirection::Right, Vec2::X),
+        ] {
+            for frame in [1, 2] {
+                let polygons = hitboxes.swing_polygons(direction, frame, origin, 50.0);
+                assert!(!polygons.is_empty());
+                assert!((centroid(&polygons) - origin).normalize().dot(front) > 0.5, "frame {}", frame);
+            }
+            // Wind-up and follow-through don't hit
+            assert!(hitboxes.swing_polygons(direction, 0, origin, 50.0).is_empty());
+            assert!(hitboxes.swing_polygons(direction, 3, origin, 50.0).is_empty());
+        }
+    }
+
+    #[test]
+    fn arcs_scale_with_reach_and_split_into_convex_pieces() {
+        let arc = HitShape::Arc { radius: 100.0, from: 0.0, to: 180.0 };
+        let polygons = arc.polygons(Vec2::ZERO, 1.5);
+        assert_eq!(polygons.len(), 2);
+        for polygon in &polygons {
+            assert_eq!(polygon[0], Vec2::ZERO);
+            for point in &polygon[1..] {
+                assert!((point.length() - 150.0).abs() < 0.01);
+                assert!(point.y >= -0.01);
+            }
+        }
+        // Its ends are the sweep's angles
+        assert!(polygons[0][1].abs_diff_eq(Vec2::new(150.0, 0.0), 0.01));
+        assert!(polygons[1].last().unwrap().abs_diff_eq(Vec2::new(-150.0, 0.0), 0.01));
+    }
+
+    #[test]
+    fn hurtboxes_cover_only_opaque_pixels() {
+        // A 2x3 blob in the top left of an 8x8 frame, whose center is the origin
+        let image = image(8, 8, &[(1, 1), (2, 1), (1, 2), (2, 2), (1, 3), (2, 3)]);
+        let bounds = opaque_bounds(&image, URect::new(0, 0, 8, 8));
+        assert_eq!(bounds, Rect::new(-3.0, 0.0, -1.0, 3.0));
+    }
+
+    #[test]
+    fn hurtboxes_are_per_frame() {
+        // Two 4x4 frames side by side, with one pixel in the bottom right of the second
+        let image = image(8, 4, &[(0, 0), (7, 3)]);
+        assert_eq!(opaque_bounds(&image, URect::new(4, 0, 8, 4)), Rect::new(1.0, -2.0, 2.0, -1.0));
+    }
+
+    #[test]
+    fn blank_frames_get_the_whole_frame() {
+        let image = image(4, 4, &[]);
+        assert_eq!(opaque_bounds(&image, URect::new(0, 0, 4, 4)), Rect::new(-2.0, -2.0, 2.0, 2.0));
+    }
+}
@@ -0,0 +1,185 @@
+// Inventory screen, opened with I while playing. Lists every sword color with the count the backend
+// holds for the player and the ones still on their way, and equips the sword the player swings. Only
+// colors with a saved balance can be equipped, besides the starter sword.
+
+use bevy::prelude::*;
+
+use crate::chain::{ChainRequest, ChainWorker};
+use crate::states::AppState;
+use crate::{capitalize, GameState};
+
+const BUTTON_COLOR: Color = Color::srgb(0.25, 0.25, 0.25);
+const BUTTON_HOVER_COLOR: Color = Color::srgb(0.35, 0.35, 0.35);
+const EQUIPPED_COLOR: Color = Color::srgb(0.2, 0.45, 0.25);
+
+// Message under the list, e.g. why a sword couldn't be equipped
+#[derive(Resource, Default)]
+pub struct InventoryMessage(String);
+
+#[derive(Component)]
+pub struct InventoryList;
+
+#[derive(Component)]
+pub struct InventoryMessageText;
+
+#[derive(Component)]
+pub struct EquipButton(u8);
+
+// I opens the inventory while playing; I or Esc closes it
+pub fn inventory_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    match state.get() {
+        AppState::Playing if keyboard.just_pressed(KeyCode::KeyI) => next_state.set(AppState::Inventory),
+        AppState::Inventory if keyboard.any_just_pressed([KeyCode::KeyI, KeyCode::Escape]) => next_state.set(AppState::Playing),
+        _ => {}
+    }
+}
+
+pub fn setup_inventory_screen(
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+    game_state: Res<GameState>,
+    chain_worker: Res<ChainWorker>,
+) {
+    time.pause();
+    commands.insert_resource(InventoryMessage::default());
+    // Shows what's cached right away and the backend's counts once they're read
+    chain_worker.send(ChainRequest::LoadInventory { colors: game_state.sword_colors.len() });
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(12.0),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.75).into(),
+                ..default()
+            },
+            StateScoped(AppState::Inventory),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Inventory", TextStyle { font_size: 48.0, color: Color::WHITE, ..default() }));
+            parent.spawn((
+                NodeBundle {
+                    style: Style { flex_direction: FlexDirection::Column, row_gap: Val::Px(6.0), ..default() },
+                    ..default()
+                },
+                InventoryList,
+            ));
+            parent.spawn((
+                TextBundle::from_section("", TextStyle { font_size: 20.0, color: Color::srgb(1.0, 0.8, 0.4), ..default() }),
+                InventoryMessageText,
+            ));
+            parent.spawn(TextBundle::from_section(
+                "Click or press 1-9 to equip    I / Esc: close",
+                TextStyle { font_size: 20.0, color: Color::WHITE, ..default() },
+            ));
+        });
+}
+
+pub fn close_inventory_screen(mut commands: Commands) {
+    commands.remove_resource::<InventoryMessage>();
+}
+
+// Rebuilds the rows whenever the counts or the equipped sword change
+pub fn update_inventory_list(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    list: Query<Entity, With<InventoryList>>,
+    added: Query<(), Added<InventoryList>>,
+) {
+    if !game_state.is_changed() && added.is_empty() {
+        return;
+    }
+    let Ok(list) = list.get_single() else {
+        return;
+    };
+    commands.entity(list).despawn_descendants().with_children(|parent| {
+        for (color, sword_color) in game_state.sword_colors.iter().enumerate() {
+            let confirmed = game_state.confirmed_swords.get(color).copied().unwrap_or(0);
+            let pending = game_state.pending_swords.get(color).copied().unwrap_or(0);
+            let equipped = game_state.swing_color as usize == color;
+            let label = format!(
+                "{}. {}: {} saved{}{}{}",
+                color + 1,
+                capitalize(&sword_color.name),
+                confirmed,
+                if pending > 0 { format!(", {} pending", pending) } else { String::new() },
+                if game_state.starter_color as usize == color { "  (starter)" } else { "" },
+                if equipped { "  (equipped)" } else { "" },
+            );
+            parent
+                .spawn((
+                    ButtonBundle {
+                        style: Style { width: Val::Px(420.0), padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)), ..default() },
+                        background_color: if equipped { EQUIPPED_COLOR.into() } else { BUTTON_COLOR.into() },
+                        ..default()
+                    },
+                    EquipButton(color as u8),
+                ))
+                .with_children(|button| {
+                    button.spawn(TextBundle::from_section(label, TextStyle { font_size: 22.0, color: Color::WHITE, ..default() }));
+                });
+        }
+    });
+}
+
+pub fn equip_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut buttons: Query<(&Interaction, &EquipButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut game_state: ResMut<GameState>,
+    mut message: ResMut<InventoryMessage>,
+) {
+    const NUMBER_KEYS: [KeyCode; 9] = [
+        KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3,
+        KeyCode::Digit4, KeyCode::Digit5, KeyCode::Digit6,
+        KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    ];
+    for (color, key) in NUMBER_KEYS.iter().enumerate() {
+        if keyboard.just_pressed(*key) {
+            equip(color as u8, &mut game_state, &mut message);
+        }
+    }
+    for (interaction, button, mut background) in buttons.iter_mut() {
+        let equipped = game_state.swing_color == button.0;
+        match interaction {
+            Interaction::Pressed => equip(button.0, &mut game_state, &mut message),
+            Interaction::Hovered if !equipped => *background = BUTTON_HOVER_COLOR.into(),
+            _ if !equipped => *background = BUTTON_COLOR.into(),
+            _ => {}
+        }
+    }
+}
+
+fn equip(color: u8, game_state: &mut GameState, message: &mut InventoryMessage) {
+    let Some(sword_color) = game_state.sword_colors.get(color as usize) else {
+        return;
+    };
+    let name = capitalize(&sword_color.name);
+    if !game_state.can_equip(color) {
+        message.0 = format!("You don't own any {} swords yet", name);
+        return;
+    }
+    if game_state.swing_color != color {
+        game_state.swing_color = color;
+        message.0 = format!("Equipped the {} sword", name);
+    }
+}
+
+pub fn update_inventory_message(message: Res<InventoryMessage>, mut text: Query<&mut Text, With<InventoryMessageText>>) {
+    if !message.is_changed() {
+        return;
+    }
+    for mut text in text.iter_mut() {
+        text.sections[0].value = message.0.clone();
+    }
+}
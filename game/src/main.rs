@@ -1,25 +1,36 @@
 use bevy::prelude::*;
 use dotenv::dotenv;
-use ethers::prelude::{Provider, Http, SignerMiddleware, LocalWallet, abigen, Middleware};
-use ethers::signers::Signer;
 use eyre::Result;
-use std::{str::FromStr, sync::Arc};
-
-
-use ethers::{
-    types::{Address, U256},
-};
-
-// Generate the contract bindings
-abigen!(
-    SwordCollection,
-    r#"[
-        function number() external view returns (uint256)
-        function increment() external
-        function getSwordCount(uint256 color) external view returns (uint256)
-        function incrementSword(uint256 color) external
-    ]"#
-);
+use rand::Rng;
+use std::{collections::HashMap, str::FromStr, time::Duration};
+use tokio::runtime::Runtime;
+
+mod animation;
+mod chain;
+mod enemies;
+mod health;
+mod hitboxes;
+mod inventory;
+mod spatial;
+mod states;
+mod toasts;
+mod wallet;
+mod waves;
+mod weapons;
+
+use animation::{AnimationTable, Animator, SpriteSheets};
+use chain::{ChainBackend, ChainEvent, ChainRequest, ChainWorker, RequestId, SwordColor};
+use chain::local::{FileBackend, LocalSave, MemoryBackend, DEFAULT_SAVE_PATH};
+use chain::rpc::{CannotMint, RpcBackend, RpcConfig, WalletSource};
+use enemies::{EnemyKind, EnemyTable, Loot, SpawnEnemy};
+use ethers::prelude::LocalWallet;
+use ethers::types::TxHash;
+use health::{Health, PLAYER_MAX_HEALTH};
+use spatial::SpatialHash;
+use hitboxes::{Hurtbox, SwingHitboxes};
+use states::{AppState, InGame, RunStats};
+use wallet::{WalletChoice, WalletPrompt};
+use weapons::{StatusEffect, WeaponStats, WeaponTable};
 
 // Game components
 #[derive(Component)]
@@ -28,33 +39,19 @@ struct Player;
 #[derive(Component)]
 struct Enemy;
 
+// Speed an enemy is being pushed with after a hit, fading out over time. Zero when it wasn't hit.
 #[derive(Component)]
-struct Sword {
-    color: u8,
-}
+struct Knockback(Vec3);
 
+// The last swing that hit an enemy, so a swing hits each enemy only once
 #[derive(Component)]
-struct SwordSwing {
-    direction: Vec3,
-    animation_frame: u8,
-    animation_timer: f32,
-}
+struct LastHit(u32);
 
 #[derive(Component)]
-struct ItemDrop {
+struct Sword {
     color: u8,
 }
 
-#[derive(Component)]
-struct AnimatedSprite {
-    current_frame: u8,
-    animation_timer: f32,
-    animation_speed: f32,
-    total_frames: u8,
-    is_swinging: bool,
-    swing_color: u8,
-}
-
 #[derive(Clone, Copy, PartialEq)]
 enum PlayerDirection {
     Up,
@@ -63,197 +60,501 @@ enum PlayerDirection {
     Right,
 }
 
-#[derive(Resource)]
-struct SpriteAssets {
-    // Player sprites (8 total: 2 for each direction)
-    player_up: Vec<Handle<Image>>,
-    player_down: Vec<Handle<Image>>,
-    player_left: Vec<Handle<Image>>,
-    player_right: Vec<Handle<Image>>,
-    
-    // Enemy sprites (2 total)
-    enemy: Vec<Handle<Image>>,
-    
-    // Sword swing sprites (16 per color: 4 for each direction)
-    sword_swings: Vec<Vec<Handle<Image>>>, // 3 colors, 16 sprites each
-    
-    // Item drop sprites (1 per color)
-    item_drops: Vec<Handle<Image>>, // 3 colors
+impl PlayerDirection {
+    // As it appears in clip names
+    fn name(self) -> &'static str {
+        match self {
+            PlayerDirection::Up => "up",
+            PlayerDirection::Down => "down",
+            PlayerDirection::Left => "left",
+            PlayerDirection::Right => "right",
+        }
+    }
 }
 
 #[derive(Resource)]
 struct GameState {
-    swords_collected: Vec<u8>,
-    contract_client: Option<Arc<SignerMiddleware<Provider<Http>, LocalWallet>>>,
-    contract_address: Option<Address>,
+    // Swords per color id that the backend has recorded
+    confirmed_swords: Vec<u64>,
+    // Swords per color id that are buffered or still on their way to the backend
+    pending_swords: Vec<u64>,
+    sword_colors: Vec<SwordColor>,
     player_position: Vec3,
     last_direction: Vec3,
     player_moving: bool,
     player_direction: PlayerDirection,
     is_swinging: bool,
     swing_frame: u8,
-    swing_timer: f32,
     swing_color: u8,
+    // Color of the starter sword, which can always be equipped even with none owned
+    starter_color: u8,
+    swing_count: u32, // Swings so far, to tell them apart
+}
+
+impl GameState {
+    // Stats of the sword the player swings
+    fn equipped_weapon<'a>(&self, weapons: &'a WeaponTable) -> &'a WeaponStats {
+        let name = self.sword_colors.get(self.swing_color as usize).map_or("", |color| color.name.as_str());
+        weapons.stats(name)
+    }
+
+    // Whether the player may swing `color`: the starter sword, or a color with a saved sword
+    fn can_equip(&self, color: u8) -> bool {
+        color == self.starter_color || self.confirmed_swords.get(color as usize).copied().unwrap_or(0) > 0
+    }
+
+    // Stops counting `swords`, given as `(color, amount)` pairs, as on their way to the backend
+    fn remove_pending(&mut self, swords: &[(u8, u64)]) {
+        for &(color, amount) in swords {
+            if let Some(pending) = self.pending_swords.get_mut(color as usize) {
+                *pending = pending.saturating_sub(amount);
+            }
+        }
+    }
+
+    // Puts the player back at the start for a new run
+    fn reset_run(&mut self) {
+        self.player_position = Vec3::ZERO;
+        self.last_direction = Vec3::new(1.0, 0.0, 0.0); // Default to facing right
+        self.player_moving = false;
+        self.player_direction = PlayerDirection::Right;
+        self.is_swinging = false;
+        self.swing_frame = 0;
+    }
+
+    // Counts `swords`, given as `(color, amount)` pairs, as on their way to the backend
+    fn add_pending(&mut self, swords: &[(u8, u64)]) {
+        for &(color, amount) in swords {
+            if let Some(pending) = self.pending_swords.get_mut(color as usize) {
+                *pending += amount;
+            }
+        }
+    }
 }
 
+// The sword counter in the top left corner
+#[derive(Component)]
+struct SwordCounterText;
+
 const PLAYER_SPEED: f32 = 400.0; // Increased from 200.0 for 4x sprites
-const ENEMY_SPAWN_RATE: f32 = 2.0;
+const KNOCKBACK_DAMPING: f32 = 8.0; // Higher stops knocked back enemies sooner
+const SWORD_PICKUP_RANGE: f32 = 60.0; // Increased from 30.0 for 4x sprites
+// Spatial hash cells, a bit bigger than an enemy. An enemy's hurtbox reaches at most half its
+// sprite from its position, 64 units for a 32 pixel frame at the default scale of 4. The hash is
+// rebuilt after enemies move, so that's the whole margin.
+const SPATIAL_CELL_SIZE: f32 = 128.0;
+const ENEMY_REACH: f32 = 64.0;
+
+// Every player starts with this sword. It's a default of the game, not a token: nothing is minted
+// for it and it doesn't count towards the inventory.
+const STARTER_SWORD: &str = "blue";
+
+const DEFAULT_FLUSH_INTERVAL_SECS: f32 = 30.0;
+const DEFAULT_FLUSH_THRESHOLD: u64 = 10;
+const DEFAULT_WATCH_INTERVAL_SECS: f32 = 5.0;
+// How long quitting waits for the last batch to be saved
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Save file of the swords collected offline, kept around while they sync to the chain
+#[derive(Resource)]
+struct LocalSync(LocalSave);
 
-fn main() -> Result<()> {
-    let rt = tokio::runtime::Runtime::new()?;
-    let game_state = rt.block_on(init_game_state())?;
+// Swords picked up but not sent yet. They go out as one batch every FLUSH_INTERVAL_SECS, once
+// FLUSH_THRESHOLD of them pile up, or when the game closes.
+#[derive(Resource)]
+struct SwordBuffer {
+    swords: Vec<u64>, // Indexed by color id
+    timer: Timer,
+    threshold: u64,
+}
+
+impl SwordBuffer {
+    fn from_env(colors: usize) -> Self {
+        let interval = std::env::var("FLUSH_INTERVAL_SECS").ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(DEFAULT_FLUSH_INTERVAL_SECS);
+        let threshold = std::env::var("FLUSH_THRESHOLD").ok()
+            .and_then(|count| count.parse().ok())
+            .unwrap_or(DEFAULT_FLUSH_THRESHOLD);
+        SwordBuffer {
+            swords: vec![0; colors],
+            timer: Timer::from_seconds(interval, TimerMode::Repeating),
+            threshold,
+        }
+    }
+
+    fn len(&self) -> u64 {
+        self.swords.iter().sum()
+    }
+
+    // Sends the buffered swords as one batch
+    fn flush(&mut self, chain_worker: &ChainWorker) {
+        self.timer.reset();
+        let swords = self.take();
+        if !swords.is_empty() {
+            chain_worker.send(ChainRequest::CollectSwords { swords });
+        }
+    }
+
+    // Empties the buffer, returning its swords as `(color, amount)` pairs
+    fn take(&mut self) -> Vec<(u8, u64)> {
+        let swords = self.swords.iter().enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(color, &count)| (color as u8, count))
+            .collect();
+        self.swords.iter_mut().for_each(|count| *count = 0);
+        swords
+    }
+}
+
+fn main() {
+    dotenv().ok();
 
     App::new()
         .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
-        .insert_resource(game_state)
-        .insert_resource(SpriteAssets {
-            player_up: Vec::new(),
-            player_down: Vec::new(),
-            player_left: Vec::new(),
-            player_right: Vec::new(),
-            enemy: Vec::new(),
-            sword_swings: vec![Vec::new(); 3],
-            item_drops: Vec::new(),
-        })
-        .add_systems(Startup, (load_assets, setup.after(load_assets)))
+        .init_state::<AppState>()
+        .add_computed_state::<InGame>()
+        .enable_state_scoped_entities::<AppState>()
+        .enable_state_scoped_entities::<InGame>()
+        .add_event::<ChainEvent>()
+        .add_event::<SpawnEnemy>()
+        .add_systems(PreUpdate, chain::forward_chain_events.run_if(resource_exists::<ChainWorker>))
+        .add_systems(PreUpdate, (wallet::close_wallet_prompt, unlock_chain).chain().run_if(resource_exists::<WalletChoice>))
+        .add_systems(Last, flush_on_exit)
+        .init_resource::<SpriteSheets>()
+        .insert_resource(hitboxes::HitboxDebug::from_env())
+        .insert_resource(SpatialHash::<Enemy>::new(SPATIAL_CELL_SIZE, ENEMY_REACH))
+        .insert_resource(SpatialHash::<Sword>::new(SPATIAL_CELL_SIZE, 0.0))
+        // Between everything that moves enemies and the systems that look them up, so lookups see
+        // this frame's positions
+        .add_systems(Update, (
+            spatial::rebuild_spatial_hash::<Enemy>
+                .after(apply_knockback).after(enemies::chase).after(enemies::keep_distance).after(enemies::dash)
+                .before(sword_collision).before(health::enemy_contact_damage),
+            spatial::rebuild_spatial_hash::<Sword>.before(collect_swords),
+        ))
+        .add_systems(Startup, (spawn_camera, toasts::setup_toasts, animation::load_animation_table, weapons::load_weapon_table, hitboxes::load_swing_hitboxes, enemies::load_enemy_table, waves::load_wave_table))
+        // Screens
+        .add_systems(OnEnter(AppState::MainMenu), states::setup_main_menu)
+        .add_systems(Update, states::main_menu_input.run_if(in_state(AppState::MainMenu)))
+        .add_systems(OnEnter(AppState::Connecting), (states::setup_connecting_screen, begin_connecting))
+        .add_systems(Update, poll_chain_connection.run_if(resource_exists::<ChainConnection>))
+        .add_systems(Update, (
+            wallet::setup_wallet_prompt.run_if(resource_added::<WalletPrompt>),
+            wallet::wallet_prompt_input,
+            wallet::wallet_prompt_buttons,
+            wallet::finish_wallet_prompt,
+            wallet::update_wallet_prompt,
+        ).chain().run_if(resource_exists::<WalletPrompt>))
+        .add_systems(OnExit(AppState::Connecting), load_assets)
+        .add_systems(OnEnter(InGame), (states::start_run, setup, health::spawn_health_bar, waves::start_waves))
+        .add_systems(OnExit(InGame), (states::end_run, waves::end_waves))
+        .add_systems(Update, states::pause_input.run_if(in_state(AppState::Playing).or_else(in_state(AppState::Paused))))
+        .add_systems(OnEnter(AppState::Paused), states::setup_pause_screen)
+        .add_systems(OnExit(AppState::Paused), states::resume_time)
+        .add_systems(Update, inventory::inventory_input.run_if(in_state(AppState::Playing).or_else(in_state(AppState::Inventory))))
+        .add_systems(OnEnter(AppState::Inventory), inventory::setup_inventory_screen)
+        .add_systems(OnExit(AppState::Inventory), (inventory::close_inventory_screen, states::resume_time))
+        .add_systems(Update, (
+            inventory::equip_input,
+            inventory::update_inventory_list,
+            inventory::update_inventory_message,
+        ).chain().run_if(in_state(AppState::Inventory)))
+        .add_systems(OnEnter(AppState::GameOver), (states::setup_game_over_screen, flush_at_game_over))
+        .add_systems(Update, states::game_over_input.run_if(in_state(AppState::GameOver)))
+        // Gameplay
         .add_systems(Update, (
             player_movement,
-            player_animation,
             sword_swing_input,
-            enemy_spawning,
-            enemy_movement,
-            enemy_animation,
-            sword_collision,
+            animation::animate.after(sword_swing_input),
+            player_animation.after(animation::animate),
+            waves::run_waves,
+            waves::update_wave_text.after(waves::run_waves),
+            apply_knockback,
+            sword_collision.after(player_animation),
+            weapons::tick_status_effects,
+            enemy_deaths.after(sword_collision).after(weapons::tick_status_effects),
             collect_swords,
+            health::enemy_contact_damage.after(apply_knockback),
+            health::player_death.after(health::enemy_contact_damage).after(enemies::projectile_hits),
+            health::tick_invulnerability,
+            health::update_health_bar,
+            states::tick_run_time,
+        ).run_if(in_state(AppState::Playing)))
+        // Hitboxes, drawn with F3
+        .add_systems(Update, hitboxes::size_hurtboxes.run_if(in_state(InGame)))
+        .add_systems(Update, (
+            hitboxes::toggle_hitbox_debug,
+            hitboxes::draw_hitboxes.run_if(|debug: Res<hitboxes::HitboxDebug>| debug.0),
+        ).chain().run_if(in_state(InGame)))
+        // Enemy behaviors
+        .add_systems(Update, (
+            enemies::chase,
+            enemies::keep_distance,
+            enemies::shoot,
+            // Its telegraph flash wins over a status effect's tint
+            enemies::dash.after(weapons::tick_status_effects),
+            enemies::split_on_death.after(sword_collision).after(weapons::tick_status_effects).before(enemy_deaths),
+            enemies::spawn_enemies.after(waves::run_waves).after(enemies::split_on_death),
+            enemies::move_projectiles,
+            enemies::projectile_hits.after(enemies::move_projectiles),
+        ).run_if(in_state(AppState::Playing)))
+        // Chain bookkeeping, in every state once the backend is up. The worker is gone once the
+        // game starts closing.
+        .add_systems(Update, (
+            flush_sword_buffer.after(collect_swords),
+            log_chain_events,
+            track_sword_counts,
+            reload_after_reorg,
+            record_synced_swords,
             update_ui,
-        ))
+            toasts::show_chain_toasts,
+            toasts::expire_toasts,
+            toasts::toast_buttons,
+        ).run_if(resource_exists::<ChainWorker>))
         .run();
+}
 
-    Ok(())
+type StartedChain = (GameState, ChainWorker, Option<LocalSync>);
+
+enum ChainStart {
+    Started(StartedChain),
+    // Waiting for the player to unlock their keystore
+    Locked(LockedChain),
 }
 
-async fn init_game_state() -> Result<GameState> {
-    dotenv().ok();
+// The contract settings, kept while the wallet prompt is up
+#[derive(Resource)]
+struct LockedChain {
+    rt: Runtime,
+    config: RpcConfig,
+    save_path: String,
+}
 
-    println!("RPC_URL: {}", std::env::var("RPC_URL").unwrap());
-    println!("STYLUS_CONTRACT_ADDRESS: {}", std::env::var("STYLUS_CONTRACT_ADDRESS").unwrap());
-    println!("PRIVATE_KEY: {}", std::env::var("PRIVATE_KEY").unwrap());
-    
-    let mut game_state = GameState {
-        swords_collected: Vec::new(),
-        contract_client: None,
-        contract_address: None,
+// Picks where swords are saved: the contract when the RPC settings are present and it can be
+// reached, otherwise the local save file, or memory only with CHAIN_BACKEND=memory. A keystore
+// wallet has to be unlocked in game first. The runtime moves into the chain worker and lives as long
+// as the game.
+fn start_chain(rt: Runtime) -> Result<ChainStart> {
+    let save_path = std::env::var("SAVE_FILE").unwrap_or_else(|_| DEFAULT_SAVE_PATH.to_string());
+
+    if std::env::var("CHAIN_BACKEND").is_ok_and(|backend| backend == "memory") {
+        let mut backend = MemoryBackend::default();
+        let game_state = rt.block_on(load_game_state(&mut backend))?;
+        return Ok(ChainStart::Started((game_state, ChainWorker::spawn(rt, backend), None)));
+    }
+
+    match RpcConfig::from_env() {
+        Some(config) => {
+            println!("RPC_URL: {}", config.rpc_url);
+            println!("STYLUS_CONTRACT_ADDRESS: {}", config.contract_address);
+            match &config.wallet {
+                WalletSource::Keystore(_) => return Ok(ChainStart::Locked(LockedChain { rt, config, save_path })),
+                WalletSource::PrivateKey(key) => match LocalWallet::from_str(key) {
+                    Ok(wallet) => return connect_chain(rt, &config, wallet, &save_path).map(ChainStart::Started),
+                    // The error could quote the key, so it's left out
+                    Err(_) => eprintln!("PRIVATE_KEY isn't a valid private key, playing offline"),
+                },
+            }
+        }
+        None => println!("No chain configured, playing offline"),
+    }
+
+    start_offline(rt, &save_path).map(ChainStart::Started)
+}
+
+// Connects to the contract as `wallet`, falling back to the save file if it can't be reached. Fails
+// if the wallet has no way to mint swords.
+fn connect_chain(rt: Runtime, config: &RpcConfig, wallet: LocalWallet, save_path: &str) -> Result<StartedChain> {
+    let connected = rt.block_on(async {
+        let mut backend = RpcBackend::connect(config, wallet).await?;
+        let game_state = load_game_state(&mut backend).await?;
+        Ok::<_, eyre::Report>((backend, game_state))
+    });
+    match connected {
+        Ok((backend, mut game_state)) => {
+            let watcher = watch_interval().and_then(|interval| backend.watcher(interval));
+            let mut worker = ChainWorker::spawn(rt, backend);
+            if let Some(watcher) = watcher {
+                worker.spawn_watcher(watcher);
+            }
+            let local_sync = sync_local_save(save_path, &worker, &mut game_state)?;
+            return Ok((game_state, worker, local_sync));
+        }
+        // A wallet that can't mint is a setup mistake, not an outage, so it isn't papered over by
+        // playing offline
+        Err(e) if e.downcast_ref::<CannotMint>().is_some() => return Err(e),
+        Err(e) => eprintln!("Couldn't reach the contract, playing offline: {}", e),
+    }
+    start_offline(rt, save_path)
+}
+
+// How often to look for inventory changes made outside the game, from WATCH_INTERVAL_SECS. Zero
+// turns the watcher off.
+fn watch_interval() -> Option<Duration> {
+    let secs = std::env::var("WATCH_INTERVAL_SECS").ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(DEFAULT_WATCH_INTERVAL_SECS);
+    (secs > 0.0).then(|| Duration::from_secs_f32(secs))
+}
+
+fn start_offline(rt: Runtime, save_path: &str) -> Result<StartedChain> {
+    println!("Saving swords to {}", save_path);
+    let mut backend = FileBackend::open(save_path)?;
+    let game_state = rt.block_on(load_game_state(&mut backend))?;
+    Ok((game_state, ChainWorker::spawn(rt, backend), None))
+}
+
+// Chain startup, run off the main thread so the connecting screen keeps drawing
+#[derive(Resource)]
+struct ChainConnection(std::thread::JoinHandle<Result<ChainStart>>);
+
+fn begin_connecting(mut commands: Commands) {
+    commands.insert_resource(ChainConnection(std::thread::spawn(|| start_chain(Runtime::new()?))));
+}
+
+// Starts playing once the chain backend is up, or brings up the wallet prompt if it's locked
+fn poll_chain_connection(world: &mut World) {
+    if !world.resource::<ChainConnection>().0.is_finished() {
+        return;
+    }
+    let Some(ChainConnection(task)) = world.remove_resource::<ChainConnection>() else {
+        return;
+    };
+    match task.join().unwrap_or_else(|_| Err(eyre::eyre!("the chain startup thread panicked"))) {
+        Ok(ChainStart::Started(started)) => {
+            insert_chain(world, started);
+            world.resource_mut::<NextState<AppState>>().set(AppState::Playing);
+        }
+        Ok(ChainStart::Locked(locked)) => {
+            if let WalletSource::Keystore(keystore) = &locked.config.wallet {
+                world.insert_resource(WalletPrompt::new(keystore.clone()));
+            }
+            world.insert_resource(locked);
+        }
+        Err(e) => {
+            eprintln!("Couldn't start the game: {}", e);
+            world.send_event(AppExit::error());
+        }
+    }
+}
+
+fn insert_chain(world: &mut World, (game_state, chain_worker, local_sync): StartedChain) {
+    world.insert_resource(SwordBuffer::from_env(game_state.sword_colors.len()));
+    world.insert_resource(game_state);
+    world.insert_resource(chain_worker);
+    if let Some(local_sync) = local_sync {
+        world.insert_resource(local_sync);
+    }
+}
+
+// Starts the chain backend once the player unlocked their wallet or chose to play offline
+fn unlock_chain(world: &mut World) {
+    let (Some(choice), Some(locked)) = (world.remove_resource::<WalletChoice>(), world.remove_resource::<LockedChain>()) else {
+        return;
+    };
+    let LockedChain { rt, config, save_path } = locked;
+    world.insert_resource(ChainConnection(std::thread::spawn(move || {
+        let started = match choice {
+            WalletChoice::Unlocked(wallet) => connect_chain(rt, &config, wallet, &save_path),
+            WalletChoice::Offline => start_offline(rt, &save_path),
+        };
+        started.map(ChainStart::Started)
+    })));
+}
+
+async fn load_game_state<B: ChainBackend>(backend: &mut B) -> Result<GameState> {
+    // The drop table and UI come from the color registry
+    let sword_colors = backend.load_colors().await?;
+    println!("Loaded {} sword colors", sword_colors.len());
+
+    let confirmed_swords = backend.load_inventory(sword_colors.len()).await?;
+    for (color, count) in confirmed_swords.iter().enumerate() {
+        println!("Loaded {} swords of color {}", count, sword_colors[color].name);
+    }
+    // Equip the first sword the player owns, or the starter sword
+    let starter_color = sword_colors.iter().position(|color| color.name == STARTER_SWORD).unwrap_or(0) as u8;
+    let swing_color = confirmed_swords.iter().position(|&count| count > 0).map_or(starter_color, |color| color as u8);
+
+    Ok(GameState {
+        pending_swords: vec![0; sword_colors.len()],
+        confirmed_swords,
+        sword_colors,
         player_position: Vec3::ZERO,
         last_direction: Vec3::new(1.0, 0.0, 0.0), // Default to facing right
         player_moving: false,
         player_direction: PlayerDirection::Right,
         is_swinging: false,
         swing_frame: 0,
-        swing_timer: 0.0,
-        swing_color: 1, // Start with blue (index 1)
-    };
-
-    if let (Ok(rpc_url), Ok(contract_addr), Ok(privkey)) = (
-        std::env::var("RPC_URL"),
-        std::env::var("STYLUS_CONTRACT_ADDRESS"),
-        std::env::var("PRIVATE_KEY"),
-    ) {
-        let provider = Provider::<Http>::try_from(rpc_url)?;
-        let wallet = LocalWallet::from_str(&privkey)?;
-        let chain_id = provider.get_chainid().await?.as_u64();
-        let client = Arc::new(SignerMiddleware::new(
-            provider,
-            wallet.with_chain_id(chain_id),
-        ));
-
-        let contract_address: Address = contract_addr.parse()?;
-        let contract = SwordCollection::new(contract_address, client.clone());
-
-        // Load existing swords
-        for color in 0u8..3u8 {
-            println!("Loading sword count for colorr: {}", color);
-            let count: U256 = contract.get_sword_count(U256::from(color)).call().await?;
-            //let count = contract.number().call().await?;
-            println!("counting: {}", count);
-            println!("fin");
-            for _ in 0..count.as_u64() {
-                game_state.swords_collected.push(color);
-            }
-        }
+        swing_color,
+        starter_color,
+        swing_count: 0,
+    })
+}
 
-        game_state.contract_client = Some(client);
-        game_state.contract_address = Some(contract_address);
+// With SYNC_LOCAL_SAVE=true, uploads the swords collected while playing offline
+fn sync_local_save(save_path: &str, worker: &ChainWorker, game_state: &mut GameState) -> Result<Option<LocalSync>> {
+    if !std::env::var("SYNC_LOCAL_SAVE").is_ok_and(|sync| sync == "true" || sync == "1") {
+        return Ok(None);
     }
-
-    Ok(game_state)
+    let save = LocalSave::open(save_path)?;
+    let unsynced = save.unsynced_swords();
+    println!("Syncing {} swords from {}", unsynced.iter().map(|&(_, amount)| amount).sum::<u64>(), save_path);
+    if !unsynced.is_empty() {
+        game_state.add_pending(&unsynced);
+        worker.send(ChainRequest::SyncSwords { swords: unsynced });
+    }
+    Ok(Some(LocalSync(save)))
 }
 
 fn load_assets(
-    _commands: Commands,
     asset_server: Res<AssetServer>,
-    mut sprite_assets: ResMut<SpriteAssets>,
+    game_state: Res<GameState>,
+    enemy_table: Res<EnemyTable>,
+    animations: Res<AnimationTable>,
+    mut sheets: ResMut<SpriteSheets>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
 ) {
-    // Load player sprites (2 frames for each direction)
-    sprite_assets.player_up.push(asset_server.load("sprites/player/up_1.png"));
-    sprite_assets.player_up.push(asset_server.load("sprites/player/up_2.png"));
-    sprite_assets.player_down.push(asset_server.load("sprites/player/down_1.png"));
-    sprite_assets.player_down.push(asset_server.load("sprites/player/down_2.png"));
-    sprite_assets.player_left.push(asset_server.load("sprites/player/left_1.png"));
-    sprite_assets.player_left.push(asset_server.load("sprites/player/left_2.png"));
-    sprite_assets.player_right.push(asset_server.load("sprites/player/right_1.png"));
-    sprite_assets.player_right.push(asset_server.load("sprites/player/right_2.png"));
-    
-    // Load enemy sprites (2 frames)
-    sprite_assets.enemy.push(asset_server.load("sprites/enemy/enemy_1.png"));
-    sprite_assets.enemy.push(asset_server.load("sprites/enemy/enemy_2.png"));
+    // Player sheet, one row per direction
+    sheets.load("player", "", &animations, &asset_server, &mut layouts);
     
-    // Load sword swing sprites (16 frames per color, 4 per direction)
-    let color_names = ["red", "blue", "green"];
-    let direction_names = ["up", "down", "left", "right"];
-    
-    for (color_idx, color_name) in color_names.iter().enumerate() {
-        sprite_assets.sword_swings[color_idx] = Vec::new();
-        for (dir_idx, dir_name) in direction_names.iter().enumerate() {
-            for frame in 0..4 {
-                let _sprite_idx = dir_idx * 4 + frame;
-                sprite_assets.sword_swings[color_idx].push(
-                    asset_server.load(&format!("sprites/swords/{}_{}_{}.png", color_name, dir_name, frame + 1))
-                );
-            }
-        }
+    // Enemy sheets, one per sprite name the enemy kinds use
+    for kind in EnemyKind::ALL {
+        sheets.load("enemy", &enemy_table.def(kind).sprite, &animations, &asset_server, &mut layouts);
     }
     
-    // Load item drop sprites (1 per color)
-    for (_color_idx, color_name) in color_names.iter().enumerate() {
-        sprite_assets.item_drops.push(asset_server.load(&format!("sprites/items/{}.png", color_name)));
+    // Sword swing and item drop sheets, named after the registry colors
+    for sword_color in &game_state.sword_colors {
+        sheets.load("sword", &sword_color.name, &animations, &asset_server, &mut layouts);
+        sheets.load("item", &sword_color.name, &animations, &asset_server, &mut layouts);
     }
 }
 
-fn setup(mut commands: Commands, sprite_assets: Res<SpriteAssets>) {
+fn spawn_camera(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
+}
+
+fn setup(mut commands: Commands, sheets: Res<SpriteSheets>, mut game_state: ResMut<GameState>) {
+    game_state.reset_run();
 
     // Check if assets are loaded
-    if sprite_assets.player_right.is_empty() {
+    let Some((texture, layout)) = sheets.get("player", "") else {
         eprintln!("Warning: Sprite assets not loaded yet!");
         return;
-    }
+    };
 
     // Player
     commands.spawn((
         SpriteBundle {
-            texture: sprite_assets.player_right[0].clone(),
+            texture: texture.clone(),
             transform: Transform::from_xyz(0.0, 0.0, 1.0).with_scale(Vec3::splat(4.0)),
             ..default()
         },
+        TextureAtlas::from(layout.clone()),
         Player,
-        AnimatedSprite {
-            current_frame: 0,
-            animation_timer: 0.0,
-            animation_speed: 8.0, // 8 FPS
-            total_frames: 2,
-            is_swinging: false,
-            swing_color: 0,
-        },
+        Health::new(PLAYER_MAX_HEALTH),
+        StateScoped(InGame),
+        Animator::new("player", "", "idle_right"),
     ));
 
     // UI
@@ -274,6 +575,8 @@ fn setup(mut commands: Commands, sprite_assets: Res<SpriteAssets>) {
             left: Val::Px(10.0),
             ..default()
         }),
+        SwordCounterText,
+        StateScoped(InGame),
     ));
 }
 
@@ -329,283 +632,382 @@ fn player_movement(
 fn sword_swing_input(
     keyboard: Res<ButtonInput<KeyCode>>,
     mut game_state: ResMut<GameState>,
+    mut player_query: Query<&mut Animator, With<Player>>,
+    weapons: Res<WeaponTable>,
+    animations: Res<AnimationTable>,
 ) {
     // Start sword swing if space is pressed and not already swinging
     if keyboard.just_pressed(KeyCode::Space) && !game_state.is_swinging {
+        let Ok(mut animator) = player_query.get_single_mut() else {
+            return;
+        };
         game_state.is_swinging = true;
         game_state.swing_frame = 0;
-        game_state.swing_timer = 0.0;
-        // Use the current swing color (starts as blue, changes when collected)
-        // game_state.swing_color is already set from collect_swords
+        game_state.swing_count += 1;
+        
+        // Swings the sword equipped in the inventory, at its own frame rate
+        let color = game_state.sword_colors.get(game_state.swing_color as usize).map_or("", |color| color.name.as_str());
+        let clip = format!("swing_{}", game_state.player_direction.name());
+        animator.play("sword", color, &clip);
+        animator.restart();
+        animator.speed = animations.clip("sword", &clip)
+            .map_or(1.0, |clip| game_state.equipped_weapon(&weapons).swing_speed / clip.fps);
     }
 }
 
-fn player_animation(
-    mut player_query: Query<(&mut Handle<Image>, &mut AnimatedSprite), With<Player>>,
-    mut game_state: ResMut<GameState>,
-    sprite_assets: Res<SpriteAssets>,
-    time: Res<Time>,
-) {
-    if let Ok((mut texture, mut animated_sprite)) = player_query.get_single_mut() {
-        // Check if assets are loaded
-        if sprite_assets.player_right.is_empty() {
+// Keeps the swing in step with its clip and walks or stands when not swinging
+fn player_animation(mut player_query: Query<&mut Animator, With<Player>>, mut game_state: ResMut<GameState>) {
+    let Ok(mut animator) = player_query.get_single_mut() else {
+        return;
+    };
+    if game_state.is_swinging {
+        if !animator.is_finished() {
+            game_state.swing_frame = animator.frame() as u8;
             return;
         }
-        
-        // Update swing animation
-        if game_state.is_swinging {
-            game_state.swing_timer += time.delta_seconds();
-            
-            // Animate at 12 FPS
-            if game_state.swing_timer >= 1.0 / 12.0 {
-                game_state.swing_frame += 1;
-                game_state.swing_timer = 0.0;
-                
-                // If swing animation is complete, stop swinging
-                if game_state.swing_frame >= 4 {
-                    game_state.is_swinging = false;
-                    animated_sprite.is_swinging = false;
-                } else {
-                    // Update to sword swing sprite
-                    let direction_idx = match game_state.player_direction {
-                        PlayerDirection::Up => 0,
-                        PlayerDirection::Down => 1,
-                        PlayerDirection::Left => 2,
-                        PlayerDirection::Right => 3,
-                    };
-                    
-                    let sprite_idx = direction_idx * 4 + game_state.swing_frame as usize;
-                    
-                    // Check bounds
-                    if (game_state.swing_color as usize) < sprite_assets.sword_swings.len() && 
-                       sprite_idx < sprite_assets.sword_swings[game_state.swing_color as usize].len() {
-                        *texture = sprite_assets.sword_swings[game_state.swing_color as usize][sprite_idx].clone();
-                    }
-                }
-            }
-        } else {
-            // Normal walking animation
-            animated_sprite.animation_timer += time.delta_seconds();
-            
-            // Get the appropriate sprite array based on direction
-            let sprite_array = match game_state.player_direction {
-                PlayerDirection::Up => &sprite_assets.player_up,
-                PlayerDirection::Down => &sprite_assets.player_down,
-                PlayerDirection::Left => &sprite_assets.player_left,
-                PlayerDirection::Right => &sprite_assets.player_right,
-            };
-            
-            // Check if the sprite array has the required frame
-            if animated_sprite.current_frame as usize >= sprite_array.len() {
-                return;
-            }
-            
-            // Update animation frame if moving
-            if game_state.player_moving && animated_sprite.animation_timer >= 1.0 / animated_sprite.animation_speed {
-                animated_sprite.current_frame = (animated_sprite.current_frame + 1) % animated_sprite.total_frames;
-                animated_sprite.animation_timer = 0.0;
-            } else if !game_state.player_moving {
-                // Reset to first frame when not moving
-                animated_sprite.current_frame = 0;
-            }
-            
-            // Update texture
-            *texture = sprite_array[animated_sprite.current_frame as usize].clone();
-        }
+        game_state.is_swinging = false;
     }
+    
+    let action = if game_state.player_moving { "walk" } else { "idle" };
+    animator.play("player", "", &format!("{}_{}", action, game_state.player_direction.name()));
 }
 
-fn enemy_spawning(
-    mut commands: Commands,
-    time: Res<Time>,
-    mut timer: Local<f32>,
-    sprite_assets: Res<SpriteAssets>,
-) {
-    // Check if assets are loaded
-    if sprite_assets.enemy.is_empty() {
-        return;
-    }
-    
-    *timer += time.delta_seconds();
-    if *timer >= ENEMY_SPAWN_RATE {
-        *timer = 0.0;
-        
-        // Spawn enemies from outside the screen
-        // Screen boundaries (assuming 800x600, but we'll use larger values for safety)
-        let screen_width = 1000.0;
-        let screen_height = 800.0;
-        
-        // Randomly choose which edge to spawn from
-        let spawn_side = rand::random::<u8>() % 4;
-        let (x, y) = match spawn_side {
-            0 => { // Top edge
-                (rand::random::<f32>() * screen_width - screen_width / 2.0, screen_height / 2.0 + 50.0)
-            },
-            1 => { // Bottom edge
-                (rand::random::<f32>() * screen_width - screen_width / 2.0, -screen_height / 2.0 - 50.0)
-            },
-            2 => { // Left edge
-                (-screen_width / 2.0 - 50.0, rand::random::<f32>() * screen_height - screen_height / 2.0)
-            },
-            _ => { // Right edge
-                (screen_width / 2.0 + 50.0, rand::random::<f32>() * screen_height - screen_height / 2.0)
-            }
-        };
-        
-        commands.spawn((
-            SpriteBundle {
-                texture: sprite_assets.enemy[0].clone(),
-                transform: Transform::from_xyz(x, y, 0.0).with_scale(Vec3::splat(4.0)),
-                ..default()
-            },
-            Enemy,
-            AnimatedSprite {
-                current_frame: 0,
-                animation_timer: 0.0,
-                animation_speed: 6.0, // 6 FPS
-                total_frames: 2,
-                is_swinging: false,
-                swing_color: 0,
-            },
-        ));
-    }
-}
-
-fn enemy_movement(
-    mut enemy_query: Query<&mut Transform, With<Enemy>>,
-    game_state: Res<GameState>,
-    time: Res<Time>,
-) {
-    for mut enemy_transform in enemy_query.iter_mut() {
-        let direction = (game_state.player_position - enemy_transform.translation).normalize();
-        enemy_transform.translation += direction * 100.0 * time.delta_seconds(); // Increased from 50.0
+// Pushes hit enemies back; how they move on their own is up to their behaviors in `enemies`
+fn apply_knockback(mut enemy_query: Query<(&mut Transform, &mut Knockback), With<Enemy>>, time: Res<Time>) {
+    for (mut enemy_transform, mut knockback) in enemy_query.iter_mut() {
+        enemy_transform.translation += knockback.0 * time.delta_seconds();
+        knockback.0 *= (1.0 - KNOCKBACK_DAMPING * time.delta_seconds()).max(0.0);
     }
 }
 
-fn enemy_animation(
-    mut enemy_query: Query<(&mut Handle<Image>, &mut AnimatedSprite), With<Enemy>>,
-    sprite_assets: Res<SpriteAssets>,
-    time: Res<Time>,
+fn sword_collision(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    weapons: Res<WeaponTable>,
+    hitboxes: Res<SwingHitboxes>,
+    // Only enemies have hurtboxes
+    mut enemy_query: Query<(Entity, &Transform, &Hurtbox, &mut Health, Option<&LastHit>)>,
+    enemy_hash: Res<SpatialHash<Enemy>>,
 ) {
-    // Check if assets are loaded
-    if sprite_assets.enemy.is_empty() {
+    if !game_state.is_swinging {
         return;
     }
     
-    for (mut texture, mut animated_sprite) in enemy_query.iter_mut() {
-        animated_sprite.animation_timer += time.delta_seconds();
-        
-        if animated_sprite.animation_timer >= 1.0 / animated_sprite.animation_speed {
-            animated_sprite.current_frame = (animated_sprite.current_frame + 1) % animated_sprite.total_frames;
-            animated_sprite.animation_timer = 0.0;
-            
-            // Check bounds
-            if animated_sprite.current_frame as usize >= sprite_assets.enemy.len() {
-                continue;
+    // What the current swing frame covers, sized by the sword's reach. Frames without shapes don't hit.
+    let weapon = game_state.equipped_weapon(&weapons);
+    let polygons = hitboxes.swing_polygons(
+        game_state.player_direction,
+        game_state.swing_frame,
+        game_state.player_position.truncate(),
+        weapon.reach,
+    );
+    // The box around everything the frame covers, to look up the enemies in it
+    let Some(area) = polygons.iter().flatten()
+        .map(|&point| Rect::from_center_size(point, Vec2::ZERO))
+        .reduce(|area, point| area.union(point)) else {
+        return;
+    };
+    
+    for candidate in enemy_hash.query_rect(area) {
+        let Ok((enemy_entity, enemy_transform, hurtbox, mut health, last_hit)) = enemy_query.get_mut(candidate) else {
+            continue;
+        };
+        if last_hit.is_some_and(|last_hit| last_hit.0 == game_state.swing_count) {
+            continue;
+        }
+        let rect = hurtbox.world_rect(enemy_transform);
+        if polygons.iter().any(|polygon| hitboxes::overlaps(polygon, rect)) {
+            health.damage(weapon.damage);
+            let away = (enemy_transform.translation - game_state.player_position).normalize_or_zero();
+            let mut enemy = commands.entity(enemy_entity);
+            enemy.insert((LastHit(game_state.swing_count), Knockback(away * weapon.knockback)));
+            if let Some(effect) = weapon.effect {
+                enemy.insert(StatusEffect::new(effect));
             }
-            
-            // Update texture
-            *texture = sprite_assets.enemy[animated_sprite.current_frame as usize].clone();
         }
     }
 }
 
-fn sword_collision(
+// Despawns enemies that ran out of health, by a sword or an effect, and drops their loot
+fn enemy_deaths(
     mut commands: Commands,
     game_state: Res<GameState>,
-    enemy_query: Query<(Entity, &Transform), With<Enemy>>,
-    sprite_assets: Res<SpriteAssets>,
+    mut run_stats: ResMut<RunStats>,
+    enemy_query: Query<(Entity, &Transform, &Health, &Loot), With<Enemy>>,
+    sheets: Res<SpriteSheets>,
 ) {
-    // Check if assets are loaded
-    if sprite_assets.item_drops.is_empty() {
-        return;
-    }
-    
-    // Only check collision if player is swinging and on the right frame (frame 1-2 are the "active" frames)
-    if !game_state.is_swinging || game_state.swing_frame < 1 || game_state.swing_frame > 2 {
-        return;
-    }
-    
-    // Calculate sword position based on player position and direction
-    let sword_offset = game_state.last_direction * 50.0; // Increased from 25.0 for 4x sprites
-    let sword_position = game_state.player_position + sword_offset;
-    
-    for (enemy_entity, enemy_transform) in enemy_query.iter() {
-        let distance = sword_position.distance(enemy_transform.translation);
-        if distance < 60.0 { // Increased from 30.0 for 4x sprites
-            commands.entity(enemy_entity).despawn();
-            
-            // Spawn sword drop
-            let color = rand::random::<u8>() % 3;
+    for (enemy_entity, enemy_transform, health, loot) in enemy_query.iter() {
+        if !health.is_dead() {
+            continue;
+        }
+        commands.entity(enemy_entity).despawn();
+        run_stats.enemies += 1;
+        
+        if rand::random::<f32>() >= loot.0.chance {
+            continue;
+        }
+        for drop in 0..loot.0.count {
+            // Spawn sword drop, weighted by the enemy's drop table or the registry's rarity weights
+            let Some(color) = pick_drop_color(&game_state.sword_colors, &loot.0.colors) else {
+                continue;
+            };
             
-            // Check bounds
-            if color as usize >= sprite_assets.item_drops.len() {
+            // Check if assets are loaded
+            let Some((texture, layout)) = game_state.sword_colors.get(color as usize)
+                .and_then(|sword_color| sheets.get("item", &sword_color.name)) else {
                 continue;
-            }
+            };
             
+            // Spread out when there's more than one
+            let offset = if drop == 0 { Vec2::ZERO } else { Vec2::from_angle(rand::random::<f32>() * std::f32::consts::TAU) * 25.0 };
             commands.spawn((
                 SpriteBundle {
-                    texture: sprite_assets.item_drops[color as usize].clone(),
+                    texture: texture.clone(),
                     transform: Transform::from_xyz(
-                        enemy_transform.translation.x,
-                        enemy_transform.translation.y,
+                        enemy_transform.translation.x + offset.x,
+                        enemy_transform.translation.y + offset.y,
                         0.0,
                     ).with_scale(Vec3::splat(2.0)),
                     ..default()
                 },
+                TextureAtlas::from(layout.clone()),
                 Sword { color },
-                ItemDrop { color },
+                StateScoped(InGame),
             ));
         }
     }
 }
 
+// Draws a drop from the enabled colors, each with probability proportional to its weight. Non-empty
+// `weights` replace the registry's, by color name, and leave out every color they don't list.
+fn pick_drop_color(sword_colors: &[SwordColor], weights: &HashMap<String, u32>) -> Option<u8> {
+    let weight = |color: &SwordColor| -> u64 {
+        if !color.enabled {
+            0
+        } else if weights.is_empty() {
+            color.weight as u64
+        } else {
+            weights.get(&color.name).copied().unwrap_or(0) as u64
+        }
+    };
+    let total: u64 = sword_colors.iter().map(weight).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut roll = rand::thread_rng().gen_range(0..total);
+    for (id, color) in sword_colors.iter().enumerate() {
+        if roll < weight(color) {
+            return Some(id as u8);
+        }
+        roll -= weight(color);
+    }
+    None
+}
+
 fn collect_swords(
     mut commands: Commands,
     mut game_state: ResMut<GameState>,
+    mut sword_buffer: ResMut<SwordBuffer>,
+    mut run_stats: ResMut<RunStats>,
     sword_query: Query<(Entity, &Transform, &Sword)>,
+    sword_hash: Res<SpatialHash<Sword>>,
 ) {
-    for (sword_entity, sword_transform, sword) in sword_query.iter() {
+    for candidate in sword_hash.query_circle(game_state.player_position.truncate(), SWORD_PICKUP_RANGE) {
+        let Ok((sword_entity, sword_transform, sword)) = sword_query.get(candidate) else {
+            continue;
+        };
         let distance = game_state.player_position.distance(sword_transform.translation);
-        if distance < 60.0 { // Increased from 30.0 for 4x sprites
-            game_state.swords_collected.push(sword.color);
-            // Change the sword color to the collected color
-            game_state.swing_color = sword.color;
+        if distance < SWORD_PICKUP_RANGE {
+            let color = sword.color as usize;
+            if let Some(pending) = game_state.pending_swords.get_mut(color) {
+                *pending += 1;
+            }
+            run_stats.swords += 1;
             commands.entity(sword_entity).despawn();
             
-            // Save to contract
-            if let (Some(client), Some(address)) = (&game_state.contract_client, game_state.contract_address) {
-                let contract = SwordCollection::new(address.clone(), client.clone());
-                let rt = tokio::runtime::Runtime::new().unwrap();
-                rt.block_on(async {
-                    if let Err(e) = contract.increment_sword(U256::from(sword.color)).send().await {
-                        eprintln!("Failed to save sword to contract: {}", e);
+            // Saved with the next batch
+            if let Some(buffered) = sword_buffer.swords.get_mut(color) {
+                *buffered += 1;
+            }
+        }
+    }
+}
+
+// Sends the buffered swords once the flush interval is up or the buffer is full
+fn flush_sword_buffer(time: Res<Time>, mut sword_buffer: ResMut<SwordBuffer>, chain_worker: Res<ChainWorker>) {
+    sword_buffer.timer.tick(time.delta());
+    if !sword_buffer.timer.just_finished() && sword_buffer.len() < sword_buffer.threshold {
+        return;
+    }
+    sword_buffer.flush(&chain_worker);
+}
+
+// A finished run is saved right away
+fn flush_at_game_over(mut sword_buffer: ResMut<SwordBuffer>, chain_worker: Option<Res<ChainWorker>>) {
+    if let Some(chain_worker) = chain_worker {
+        sword_buffer.flush(&chain_worker);
+    }
+}
+
+// When the game is closing, sends whatever is still buffered and waits for the worker to save it
+fn flush_on_exit(world: &mut World) {
+    if world.resource::<Events<AppExit>>().is_empty() {
+        return;
+    }
+    let Some(chain_worker) = world.remove_resource::<ChainWorker>() else {
+        return;
+    };
+    let swords = world.resource_mut::<SwordBuffer>().take();
+    if !swords.is_empty() {
+        println!("Saving {} buffered swords before quitting", swords.iter().map(|&(_, amount)| amount).sum::<u64>());
+        chain_worker.send(ChainRequest::CollectSwords { swords });
+    }
+    let events = chain_worker.shutdown(SHUTDOWN_TIMEOUT);
+    let mut local_sync = world.remove_resource::<LocalSync>();
+    for event in &events {
+        log_chain_event(event);
+        if let Some(local_sync) = local_sync.as_mut() {
+            mark_synced(local_sync, event);
+        }
+    }
+}
+
+fn log_chain_events(mut events: EventReader<ChainEvent>) {
+    for event in events.read() {
+        log_chain_event(event);
+    }
+}
+
+fn log_chain_event(event: &ChainEvent) {
+    match event {
+        ChainEvent::Submitted { request, tx_hash, .. } => println!("Sent {:?} in {:?}", request, tx_hash),
+        ChainEvent::Confirmed { request, tx_hash, block, .. } => println!("Confirmed {:?} in {:?} (block {:?})", request, tx_hash, block),
+        ChainEvent::Failed { request, error, .. } => eprintln!("Failed {:?}: {}", request, error),
+        ChainEvent::InventoryLoaded { counts, .. } => println!("Loaded inventory {:?}", counts),
+        ChainEvent::InventoryChanged { tx_hash, block, changes } => println!("Inventory changed by {:?} (block {}): {:?}", tx_hash, block, changes),
+        ChainEvent::Reorged { block } => println!("Chain reorganized below block {}, reloading inventory", block),
+    }
+}
+
+// Moves swords from pending to confirmed once they're saved, and drops the ones that failed. A
+// reloaded inventory replaces the confirmed counts.
+fn track_sword_counts(
+    mut events: EventReader<ChainEvent>,
+    mut game_state: ResMut<GameState>,
+    chain_worker: Res<ChainWorker>,
+    // Transactions the game sent and the request each belongs to, so the watcher's reports of them
+    // aren't counted twice. Only kept while a watcher runs, and forgotten once the watcher reports
+    // them or they're known to have minted nothing.
+    mut sent: Local<HashMap<TxHash, RequestId>>,
+) {
+    for event in events.read() {
+        let (request, confirmed) = match event {
+            ChainEvent::Confirmed { request, .. } => (request, true),
+            ChainEvent::Failed { id, request, unconfirmed, .. } => {
+                // Without an unconfirmed transaction the request's transactions never minted, so no
+                // logs are coming for them
+                if unconfirmed.is_none() {
+                    sent.retain(|_, request_id| request_id != id);
+                }
+                (request, false)
+            }
+            ChainEvent::Submitted { id, tx_hash, .. } => {
+                if chain_worker.is_watching() {
+                    sent.insert(*tx_hash, *id);
+                }
+                continue;
+            }
+            ChainEvent::InventoryChanged { tx_hash, changes, .. } => {
+                if sent.remove(tx_hash).is_some() {
+                    continue;
+                }
+                for &(color, change) in changes {
+                    if let Some(count) = game_state.confirmed_swords.get_mut(color as usize) {
+                        *count = count.saturating_add_signed(change);
                     }
-                });
+                }
+                continue;
+            }
+            ChainEvent::Reorged { .. } => continue,
+            ChainEvent::InventoryLoaded { counts, .. } => {
+                // The worker handles requests in order, so every earlier save is already counted
+                let colors = game_state.sword_colors.len();
+                game_state.confirmed_swords = counts.clone();
+                game_state.confirmed_swords.resize(colors, 0);
+                continue;
+            }
+        };
+        game_state.remove_pending(request.swords());
+        if confirmed {
+            for &(color, amount) in request.swords() {
+                if let Some(count) = game_state.confirmed_swords.get_mut(color as usize) {
+                    *count += amount;
+                }
             }
         }
     }
 }
 
-fn update_ui(mut text_query: Query<&mut Text>, game_state: Res<GameState>) {
+// Reads the counts again after a reorg. Going through the worker queues it behind the transactions
+// already sent, like the inventory screen's reads.
+fn reload_after_reorg(mut events: EventReader<ChainEvent>, game_state: Res<GameState>, chain_worker: Res<ChainWorker>) {
+    if events.read().any(|event| matches!(event, ChainEvent::Reorged { .. })) {
+        chain_worker.send(ChainRequest::LoadInventory { colors: game_state.sword_colors.len() });
+    }
+}
+
+// Takes synced swords off the local save, so they're only uploaded once
+fn record_synced_swords(mut events: EventReader<ChainEvent>, local_sync: Option<ResMut<LocalSync>>) {
+    let Some(mut local_sync) = local_sync else {
+        return;
+    };
+    for event in events.read() {
+        mark_synced(&mut local_sync, event);
+    }
+}
+
+fn mark_synced(local_sync: &mut LocalSync, event: &ChainEvent) {
+    if let ChainEvent::Confirmed { request: ChainRequest::SyncSwords { swords }, .. } = event {
+        if let Err(e) = local_sync.0.mark_synced(swords) {
+            eprintln!("Failed to update local save: {}", e);
+        }
+    }
+}
+
+fn update_ui(mut text_query: Query<&mut Text, With<SwordCounterText>>, game_state: Res<GameState>, chain_worker: Res<ChainWorker>) {
     if game_state.is_changed() {
-        // Count swords by color
-        let mut color_counts = [0u32; 3];
-        for &color in &game_state.swords_collected {
-            color_counts[color as usize] += 1;
+        // Build the display text, with swords that aren't saved yet in parentheses
+        let confirmed: u64 = game_state.confirmed_swords.iter().sum();
+        let pending: u64 = game_state.pending_swords.iter().sum();
+        let mut display_text = format!("Total Swords: {}{}\n", confirmed, pending_suffix(pending));
+        for (color, sword_color) in game_state.sword_colors.iter().enumerate() {
+            let confirmed = game_state.confirmed_swords.get(color).copied().unwrap_or(0);
+            let pending = game_state.pending_swords.get(color).copied().unwrap_or(0);
+            display_text.push_str(&format!("{}: {}{} ", capitalize(&sword_color.name), confirmed, pending_suffix(pending)));
         }
-        
-        // Create color names
-        let color_names = ["Red", "Blue", "Green"];
-        
-        // Build the display text
-        let mut display_text = format!("Total Swords: {}\n", game_state.swords_collected.len());
-        for (name, count) in color_names.iter().zip(color_counts.iter()) {
-            display_text.push_str(&format!("{}: {} ", name, count));
+        if let Some(equipped) = game_state.sword_colors.get(game_state.swing_color as usize) {
+            display_text.push_str(&format!("\nEquipped: {} (I: inventory)", capitalize(&equipped.name)));
         }
+        display_text.push_str(&format!("\nSaving to {}", chain_worker.backend_name));
         
         for mut text in text_query.iter_mut() {
             text.sections[0].value = display_text.clone();
         }
     }
 }
+
+fn pending_suffix(pending: u64) -> String {
+    if pending > 0 {
+        format!(" (+{} pending)", pending)
+    } else {
+        String::new()
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,132 @@
+// Uniform grid spatial hash, so systems that look for entities near a point or inside a box only
+// visit the cells around it instead of every entity. There is one hash per marker component
+// (enemies, pickups), rebuilt from their positions every frame. Queries return candidates; callers
+// still run their exact test on them.
+//
+// The sword's hit lookup, enemy contact damage and sword pickups use it. Systems that act on every
+// enemy anyway, like movement and the other behaviors, still iterate all of them.
+//
+// Only depends on Bevy, so `examples/spatial_bench.rs` can include it as is.
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::marker::PhantomData;
+
+#[derive(Resource)]
+pub struct SpatialHash<T> {
+    cell_size: f32,
+    // How far an entity can reach from its position, added around every query
+    margin: f32,
+    cells: HashMap<IVec2, Vec<Entity>>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> SpatialHash<T> {
+    pub fn new(cell_size: f32, margin: f32) -> Self {
+        SpatialHash { cell_size, margin, cells: HashMap::new(), marker: PhantomData }
+    }
+
+    fn cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+
+    // Empties the grid, keeping the allocations of the cells that were in use
+    pub fn clear(&mut self) {
+        self.cells.retain(|_, entities| {
+            let used = !entities.is_empty();
+            entities.clear();
+            used
+        });
+    }
+
+    pub fn insert(&mut self, entity: Entity, position: Vec2) {
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_default().push(entity);
+    }
+
+    // Entities that may overlap `rect`
+    pub fn query_rect(&self, rect: Rect) -> impl Iterator<Item = Entity> + '_ {
+        let rect = rect.inflate(self.margin);
+        let (min, max) = (self.cell(rect.min), self.cell(rect.max));
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+
+    // Entities that may be within `radius` of `center`
+    pub fn query_circle(&self, center: Vec2, radius: f32) -> impl Iterator<Item = Entity> + '_ {
+        self.query_rect(Rect::from_center_half_size(center, Vec2::splat(radius)))
+    }
+}
+
+pub fn rebuild_spatial_hash<T: Component>(mut hash: ResMut<SpatialHash<T>>, query: Query<(Entity, &Transform), With<T>>) {
+    hash.clear();
+    for (entity, transform) in query.iter() {
+        hash.insert(entity, transform.translation.truncate());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Marker;
+
+    fn found(hash: &SpatialHash<Marker>, rect: Rect) -> Vec<Entity> {
+        let mut found: Vec<Entity> = hash.query_rect(rect).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn queries_span_the_cells_they_cover() {
+        let mut hash = SpatialHash::<Marker>::new(10.0, 0.0);
+        let (left, right, far) = (Entity::from_raw(0), Entity::from_raw(1), Entity::from_raw(2));
+        hash.insert(left, Vec2::new(9.5, 5.0));
+        hash.insert(right, Vec2::new(10.5, 5.0));
+        hash.insert(far, Vec2::new(35.0, 5.0));
+        // Straddling the boundary between the first two cells
+        assert_eq!(found(&hash, Rect::new(9.0, 4.0, 11.0, 6.0)), vec![left, right]);
+        // Inside one cell
+        assert_eq!(found(&hash, Rect::new(11.0, 1.0, 12.0, 2.0)), vec![right]);
+        assert_eq!(found(&hash, Rect::new(21.0, 1.0, 29.0, 9.0)), vec![]);
+    }
+
+    #[test]
+    fn negative_coordinates_get_their_own_cells() {
+        let mut hash = SpatialHash::<Marker>::new(10.0, 0.0);
+        let (negative, positive) = (Entity::from_raw(0), Entity::from_raw(1));
+        // Truncating instead of flooring would put both in cell 0
+        hash.insert(negative, Vec2::new(-0.5, -0.5));
+        hash.insert(positive, Vec2::new(0.5, 0.5));
+        assert_eq!(found(&hash, Rect::new(-9.0, -9.0, -1.0, -1.0)), vec![negative]);
+        assert_eq!(found(&hash, Rect::new(1.0, 1.0, 9.0, 9.0)), vec![positive]);
+        assert_eq!(found(&hash, Rect::new(-1.0, -1.0, 1.0, 1.0)), vec![negative, positive]);
+    }
+
+    #[test]
+    fn the_margin_reaches_into_neighbouring_cells() {
+        let mut without = SpatialHash::<Marker>::new(10.0, 0.0);
+        let mut with = SpatialHash::<Marker>::new(10.0, 6.0);
+        let enemy = Entity::from_raw(0);
+        without.insert(enemy, Vec2::new(25.0, 5.0));
+        with.insert(enemy, Vec2::new(25.0, 5.0));
+        // A query a few units short of its cell only finds it when the margin covers the gap
+        let rect = Rect::new(11.0, 1.0, 15.0, 9.0);
+        assert_eq!(found(&without, rect), vec![]);
+        assert_eq!(found(&with, rect), vec![enemy]);
+        assert_eq!(with.query_circle(Vec2::new(5.0, -10.0), 5.0).count(), 0);
+    }
+
+    #[test]
+    fn clearing_empties_every_cell() {
+        let mut hash = SpatialHash::<Marker>::new(10.0, 0.0);
+        hash.insert(Entity::from_raw(0), Vec2::new(5.0, 5.0));
+        hash.clear();
+        assert_eq!(hash.query_rect(Rect::new(0.0, 0.0, 10.0, 10.0)).count(), 0);
+        hash.insert(Entity::from_raw(1), Vec2::new(5.0, 5.0));
+        assert_eq!(found(&hash, Rect::new(0.0, 0.0, 10.0, 10.0)), vec![Entity::from_raw(1)]);
+    }
+}
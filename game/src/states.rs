@@ -0,0 +1,165 @@
+// The game's screens and how it moves between them: main menu, connecting to the chain (with the
+// wallet prompt when needed), playing, paused, the inventory and game over. Every screen is despawned when its
+// state exits, and everything belonging to a run when the run ends.
+
+use bevy::prelude::*;
+
+use crate::chain::ChainWorker;
+
+#[derive(States, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum AppState {
+    #[default]
+    MainMenu,
+    Connecting,
+    Playing,
+    Paused,
+    Inventory,
+    GameOver,
+}
+
+// Active from the moment a run starts until the player leaves its game over screen
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct InGame;
+
+impl ComputedStates for InGame {
+    type SourceStates = AppState;
+
+    fn compute(state: AppState) -> Option<Self> {
+        match state {
+            AppState::Playing | AppState::Paused | AppState::Inventory | AppState::GameOver => Some(InGame),
+            AppState::MainMenu | AppState::Connecting => None,
+        }
+    }
+}
+
+// What the player did this run, shown on the game over screen
+#[derive(Resource, Default)]
+pub struct RunStats {
+    pub swords: u64,
+    pub enemies: u64,
+    pub time: f32,
+    pub waves_cleared: u32,
+    pub died: bool,
+    // Swords collected but not yet sent when the player died
+    pub swords_lost: u64,
+}
+
+pub fn setup_main_menu(mut commands: Commands) {
+    spawn_screen(&mut commands, AppState::MainMenu, "Sword Collection", "Enter: play    Esc: quit");
+}
+
+pub fn main_menu_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    chain_worker: Option<Res<ChainWorker>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if keyboard.just_pressed(KeyCode::Enter) {
+        // The chain only has to be connected once per launch
+        next_state.set(if chain_worker.is_some() { AppState::Playing } else { AppState::Connecting });
+    } else if keyboard.just_pressed(KeyCode::Escape) {
+        exit.send(AppExit::Success);
+    }
+}
+
+pub fn setup_connecting_screen(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("Connecting...", TextStyle { font_size: 24.0, color: Color::WHITE, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            }),
+        StateScoped(AppState::Connecting),
+    ));
+}
+
+pub fn start_run(mut commands: Commands) {
+    commands.insert_resource(RunStats::default());
+}
+
+pub fn end_run(mut commands: Commands) {
+    commands.remove_resource::<RunStats>();
+}
+
+pub fn tick_run_time(time: Res<Time>, mut run_stats: ResMut<RunStats>) {
+    run_stats.time += time.delta_seconds();
+}
+
+// Esc pauses and resumes; while paused, Q ends the run
+pub fn pause_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    match state.get() {
+        AppState::Playing if keyboard.just_pressed(KeyCode::Escape) => next_state.set(AppState::Paused),
+        AppState::Paused if keyboard.just_pressed(KeyCode::Escape) => next_state.set(AppState::Playing),
+        AppState::Paused if keyboard.just_pressed(KeyCode::KeyQ) => next_state.set(AppState::GameOver),
+        _ => {}
+    }
+}
+
+pub fn setup_pause_screen(mut commands: Commands, mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+    spawn_screen(&mut commands, AppState::Paused, "Paused", "Esc: resume    Q: end run");
+}
+
+pub fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+pub fn setup_game_over_screen(mut commands: Commands, run_stats: Res<RunStats>) {
+    let seconds = run_stats.time as u64;
+    let lost = if run_stats.swords_lost > 0 {
+        format!("\nUnsaved swords lost: {}", run_stats.swords_lost)
+    } else {
+        String::new()
+    };
+    let summary = format!(
+        "Swords collected: {}{}\nEnemies defeated: {}\nWaves cleared: {}\nTime: {}:{:02}\n\nEnter: back to menu",
+        run_stats.swords,
+        lost,
+        run_stats.enemies,
+        run_stats.waves_cleared,
+        seconds / 60,
+        seconds % 60,
+    );
+    let title = if run_stats.died { "You Died" } else { "Game Over" };
+    spawn_screen(&mut commands, AppState::GameOver, title, &summary);
+}
+
+pub fn game_over_input(keyboard: Res<ButtonInput<KeyCode>>, mut next_state: ResMut<NextState<AppState>>) {
+    if keyboard.just_pressed(KeyCode::Enter) {
+        next_state.set(AppState::MainMenu);
+    }
+}
+
+// Centered title and text that live as long as `state`
+fn spawn_screen(commands: &mut Commands, state: AppState, title: &str, text: &str) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(16.0),
+                    ..default()
+                },
+                background_color: Color::srgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..default()
+            },
+            StateScoped(state),
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(title, TextStyle { font_size: 48.0, color: Color::WHITE, ..default() }));
+            parent.spawn(
+                TextBundle::from_section(text, TextStyle { font_size: 24.0, color: Color::WHITE, ..default() })
+                    .with_text_justify(JustifyText::Center),
+            );
+        });
+}
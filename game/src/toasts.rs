@@ -0,0 +1,237 @@
+// On-screen notifications for the chain worker's transactions. Each request gets a toast that goes
+// from pending to confirmed (with its transaction hash and block) or failed, and failed ones can be
+// retried or dismissed from the toast itself.
+
+use bevy::prelude::*;
+use ethers::types::TxHash;
+
+use crate::chain::{ChainEvent, ChainRequest, ChainWorker, RequestId};
+use crate::GameState;
+
+// How long a confirmed toast stays up. Failed ones stay until the player deals with them.
+const CONFIRMED_TOAST_SECS: f32 = 6.0;
+
+const PENDING_COLOR: Color = Color::srgba(0.15, 0.15, 0.2, 0.85);
+const CONFIRMED_COLOR: Color = Color::srgba(0.1, 0.35, 0.15, 0.85);
+const FAILED_COLOR: Color = Color::srgba(0.45, 0.1, 0.1, 0.9);
+const BUTTON_COLOR: Color = Color::srgb(0.25, 0.25, 0.25);
+const BUTTON_HOVER_COLOR: Color = Color::srgb(0.35, 0.35, 0.35);
+
+// Column in the top right corner the toasts stack in
+#[derive(Component)]
+pub struct ToastStack;
+
+#[derive(Component)]
+pub struct Toast {
+    id: RequestId,
+    request: ChainRequest,
+    // The transaction of the last failure that may still have gone through
+    unconfirmed: Option<TxHash>,
+    expires: Option<Timer>,
+}
+
+#[derive(Component)]
+pub struct ToastButton {
+    toast: Entity,
+    action: ToastAction,
+}
+
+#[derive(Clone, Copy)]
+enum ToastAction {
+    Retry,
+    Dismiss,
+}
+
+enum ToastStatus<'a> {
+    Pending(Option<TxHash>),
+    Confirmed { tx_hash: Option<TxHash>, block: Option<u64> },
+    Failed { error: &'a str, unconfirmed: Option<TxHash> },
+}
+
+pub fn setup_toasts(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                right: Val::Px(10.0),
+                width: Val::Px(420.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                ..default()
+            },
+            ..default()
+        },
+        ToastStack,
+    ));
+}
+
+// Creates or updates the toast of every request the worker reported on
+pub fn show_chain_toasts(
+    mut commands: Commands,
+    mut events: EventReader<ChainEvent>,
+    mut toasts: Query<(Entity, &mut Toast)>,
+    stack: Query<Entity, With<ToastStack>>,
+    chain_worker: Res<ChainWorker>,
+) {
+    let Ok(stack) = stack.get_single() else {
+        return;
+    };
+    for event in events.read() {
+        let (id, request, status) = match event {
+            ChainEvent::Submitted { id, request, tx_hash } => (*id, request, ToastStatus::Pending(Some(*tx_hash))),
+            ChainEvent::Confirmed { id, request, tx_hash, block } => (*id, request, ToastStatus::Confirmed { tx_hash: *tx_hash, block: *block }),
+            ChainEvent::Failed { id, request, error, unconfirmed } => (*id, request, ToastStatus::Failed { error, unconfirmed: *unconfirmed }),
+            ChainEvent::InventoryLoaded { .. } | ChainEvent::InventoryChanged { .. } | ChainEvent::Reorged { .. } => continue,
+        };
+        // Only saves get a toast
+        if let ChainRequest::LoadInventory { .. } = request {
+            continue;
+        }
+        let entity = match toasts.iter_mut().find(|(_, toast)| toast.id == id) {
+            Some((entity, mut toast)) => {
+                toast.expires = expiry(&status);
+                toast.unconfirmed = unconfirmed(&status);
+                entity
+            }
+            None => {
+                let toast = Toast { id, request: request.clone(), unconfirmed: unconfirmed(&status), expires: expiry(&status) };
+                let entity = commands.spawn(toast).id();
+                commands.entity(stack).add_child(entity);
+                entity
+            }
+        };
+        build_toast(&mut commands, entity, request, &status, chain_worker.backend_name);
+    }
+}
+
+// Removes confirmed toasts once they've been up for a while
+pub fn expire_toasts(mut commands: Commands, time: Res<Time>, mut toasts: Query<(Entity, &mut Toast)>) {
+    for (entity, mut toast) in toasts.iter_mut() {
+        if let Some(expires) = toast.expires.as_mut() {
+            if expires.tick(time.delta()).finished() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+// Handles the retry and dismiss buttons of failed toasts
+pub fn toast_buttons(
+    mut commands: Commands,
+    mut buttons: Query<(&Interaction, &ToastButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut toasts: Query<&mut Toast>,
+    mut game_state: ResMut<GameState>,
+    chain_worker: Res<ChainWorker>,
+) {
+    for (interaction, button, mut background) in buttons.iter_mut() {
+        match interaction {
+            Interaction::Hovered => *background = BUTTON_HOVER_COLOR.into(),
+            Interaction::None => *background = BUTTON_COLOR.into(),
+            Interaction::Pressed => {
+                let Ok(mut toast) = toasts.get_mut(button.toast) else {
+                    continue;
+                };
+                match button.action {
+                    ToastAction::Retry => {
+                        // The failure took these swords off the pending count. If its transaction may
+                        // have gone through, the worker checks it before sending them again.
+                        game_state.add_pending(toast.request.swords());
+                        toast.id = chain_worker.retry(toast.request.clone(), toast.unconfirmed);
+                        toast.expires = None;
+                        build_toast(&mut commands, button.toast, &toast.request, &ToastStatus::Pending(None), chain_worker.backend_name);
+                    }
+                    ToastAction::Dismiss => commands.entity(button.toast).despawn_recursive(),
+                }
+            }
+        }
+    }
+}
+
+fn unconfirmed(status: &ToastStatus) -> Option<TxHash> {
+    match status {
+        ToastStatus::Failed { unconfirmed, .. } => *unconfirmed,
+        _ => None,
+    }
+}
+
+fn expiry(status: &ToastStatus) -> Option<Timer> {
+    match status {
+        ToastStatus::Confirmed { .. } => Some(Timer::from_seconds(CONFIRMED_TOAST_SECS, TimerMode::Once)),
+        _ => None,
+    }
+}
+
+// Replaces the contents of `toast` with the message for `status`
+fn build_toast(commands: &mut Commands, toast: Entity, request: &ChainRequest, status: &ToastStatus, backend_name: &str) {
+    let swords = describe_request(request);
+    let (message, background) = match status {
+        ToastStatus::Pending(None) => (format!("Saving {}...", swords), PENDING_COLOR),
+        ToastStatus::Pending(Some(tx_hash)) => (format!("Saving {}...\ntx {}", swords, short_hash(tx_hash)), PENDING_COLOR),
+        ToastStatus::Confirmed { tx_hash: Some(tx_hash), block } => {
+            let block = block.map(|block| format!(", block {}", block)).unwrap_or_default();
+            (format!("Saved {} on-chain\ntx {}{}", swords, short_hash(tx_hash), block), CONFIRMED_COLOR)
+        }
+        ToastStatus::Confirmed { tx_hash: None, .. } => (format!("Saved {} to {}", swords, backend_name), CONFIRMED_COLOR),
+        ToastStatus::Failed { error, unconfirmed: None } => (format!("Couldn't save {}:\n{}", swords, error), FAILED_COLOR),
+        ToastStatus::Failed { error, unconfirmed: Some(_) } => {
+            (format!("Couldn't confirm {}:\n{}\nRetry checks whether it went through first", swords, error), FAILED_COLOR)
+        }
+    };
+
+    commands.entity(toast).despawn_descendants().insert(NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(8.0)),
+            row_gap: Val::Px(6.0),
+            ..default()
+        },
+        background_color: background.into(),
+        ..default()
+    });
+    commands.entity(toast).with_children(|parent| {
+        parent.spawn(TextBundle::from_section(message, TextStyle { font_size: 18.0, color: Color::WHITE, ..default() }));
+        if let ToastStatus::Failed { .. } = status {
+            parent
+                .spawn(NodeBundle {
+                    style: Style { column_gap: Val::Px(6.0), ..default() },
+                    ..default()
+                })
+                .with_children(|row| {
+                    spawn_button(row, toast, ToastAction::Retry, "Retry");
+                    spawn_button(row, toast, ToastAction::Dismiss, "Dismiss");
+                });
+        }
+    });
+}
+
+fn spawn_button(parent: &mut ChildBuilder, toast: Entity, action: ToastAction, label: &str) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style { padding: UiRect::axes(Val::Px(10.0), Val::Px(4.0)), ..default() },
+                background_color: BUTTON_COLOR.into(),
+                ..default()
+            },
+            ToastButton { toast, action },
+        ))
+        .with_children(|button| {
+            button.spawn(TextBundle::from_section(label, TextStyle { font_size: 16.0, color: Color::WHITE, ..default() }));
+        });
+}
+
+fn describe_request(request: &ChainRequest) -> String {
+    let count: u64 = request.swords().iter().map(|&(_, amount)| amount).sum();
+    let swords = if count == 1 { "1 sword".to_string() } else { format!("{} swords", count) };
+    match request {
+        ChainRequest::CollectSwords { .. } => swords,
+        ChainRequest::SyncSwords { .. } => format!("{} from the offline save", swords),
+        ChainRequest::LoadInventory { .. } => "inventory".to_string(),
+    }
+}
+
+// Shortens a hash to its first and last few digits, e.g. 0x1234ab...cdef
+fn short_hash(tx_hash: &TxHash) -> String {
+    let hex = format!("{:#x}", tx_hash);
+    format!("{}...{}", &hex[..8], &hex[hex.len() - 4..])
+}
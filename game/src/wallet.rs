@@ -0,0 +1,228 @@
+// Password prompt that unlocks the player's wallet from an encrypted JSON keystore, so the key never
+// has to sit in `.env` in plaintext. Players without a wallet can create a burner one here, which is
+// exported as a keystore file they can import elsewhere. The key itself is never printed.
+
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::prelude::*;
+use ethers::prelude::LocalWallet;
+use ethers::signers::Signer;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+
+const BUTTON_COLOR: Color = Color::srgb(0.25, 0.25, 0.25);
+const BUTTON_HOVER_COLOR: Color = Color::srgb(0.35, 0.35, 0.35);
+
+// What the player chose in the prompt, picked up by `main` to start the chain backend
+#[derive(Resource)]
+pub enum WalletChoice {
+    Unlocked(LocalWallet),
+    Offline,
+}
+
+#[derive(Resource)]
+pub struct WalletPrompt {
+    keystore: PathBuf,
+    password: String,
+    status: String,
+    // Decrypting or creating a keystore takes a moment, so it runs on its own thread
+    task: Option<JoinHandle<Result<LocalWallet, String>>>,
+}
+
+impl WalletPrompt {
+    pub fn new(keystore: PathBuf) -> Self {
+        let status = if keystore.exists() {
+            format!("Enter the password of {}", keystore.display())
+        } else {
+            format!("No wallet at {} yet, pick a password and create a burner wallet", keystore.display())
+        };
+        WalletPrompt { keystore, password: String::new(), status, task: None }
+    }
+
+    fn start(&mut self, action: WalletAction) {
+        if self.task.is_some() {
+            return;
+        }
+        if self.password.is_empty() && action != WalletAction::Offline {
+            self.status = "Type a password first".to_string();
+            return;
+        }
+        let keystore = self.keystore.clone();
+        let password = std::mem::take(&mut self.password);
+        self.task = match action {
+            WalletAction::Unlock => {
+                self.status = "Unlocking wallet...".to_string();
+                Some(std::thread::spawn(move || {
+                    LocalWallet::decrypt_keystore(&keystore, password)
+                        .map_err(|e| format!("Couldn't unlock {}: {}", keystore.display(), e))
+                }))
+            }
+            WalletAction::Burner => {
+                self.status = "Creating burner wallet...".to_string();
+                Some(std::thread::spawn(move || create_burner(&keystore, &password).map_err(|e| format!("Couldn't create a burner wallet: {}", e))))
+            }
+            WalletAction::Offline => None,
+        };
+    }
+}
+
+// Generates a new wallet and exports it encrypted with `password`, at `keystore` unless there's a
+// wallet there already, in which case it goes next to it
+fn create_burner(keystore: &Path, password: &str) -> eyre::Result<LocalWallet> {
+    let dir = match keystore.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir)?;
+    let (wallet, uuid) = LocalWallet::new_keystore(dir, &mut rand::thread_rng(), password, None)?;
+    let path = if keystore.exists() {
+        dir.join(format!("burner-{:?}.json", wallet.address()))
+    } else {
+        keystore.to_path_buf()
+    };
+    std::fs::rename(dir.join(uuid), &path)?;
+    println!("Created burner wallet {:?}, exported to {}", wallet.address(), path.display());
+    Ok(wallet)
+}
+
+#[derive(Component)]
+pub struct WalletPromptUi;
+
+#[derive(Component)]
+pub struct PasswordText;
+
+#[derive(Component)]
+pub struct WalletStatusText;
+
+#[derive(Component, Clone, Copy, PartialEq)]
+pub enum WalletAction {
+    Unlock,
+    Burner,
+    Offline,
+}
+
+pub fn setup_wallet_prompt(mut commands: Commands) {
+    let text_style = TextStyle { font_size: 24.0, color: Color::WHITE, ..default() };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(12.0),
+                    ..default()
+                },
+                ..default()
+            },
+            WalletPromptUi,
+        ))
+        .with_children(|parent| {
+            parent.spawn((TextBundle::from_section("", text_style.clone()), WalletStatusText));
+            parent.spawn((
+                TextBundle::from_section("Password: ", text_style.clone()),
+                PasswordText,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style { column_gap: Val::Px(8.0), ..default() },
+                    ..default()
+                })
+                .with_children(|row| {
+                    spawn_button(row, WalletAction::Unlock, "Unlock (Enter)");
+                    spawn_button(row, WalletAction::Burner, "New burner wallet");
+                    spawn_button(row, WalletAction::Offline, "Play offline");
+                });
+        });
+}
+
+fn spawn_button(parent: &mut ChildBuilder, action: WalletAction, label: &str) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style { padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)), ..default() },
+                background_color: BUTTON_COLOR.into(),
+                ..default()
+            },
+            action,
+        ))
+        .with_children(|button| {
+            button.spawn(TextBundle::from_section(label, TextStyle { font_size: 20.0, color: Color::WHITE, ..default() }));
+        });
+}
+
+// Types the password, masked, and submits it with Enter
+pub fn wallet_prompt_input(mut keys: EventReader<KeyboardInput>, mut prompt: ResMut<WalletPrompt>) {
+    for key in keys.read() {
+        if !key.state.is_pressed() || prompt.task.is_some() {
+            continue;
+        }
+        match &key.logical_key {
+            Key::Character(text) => prompt.password.push_str(text),
+            Key::Space => prompt.password.push(' '),
+            Key::Backspace => {
+                prompt.password.pop();
+            }
+            Key::Enter => prompt.start(WalletAction::Unlock),
+            _ => {}
+        }
+    }
+}
+
+pub fn wallet_prompt_buttons(
+    mut commands: Commands,
+    mut buttons: Query<(&Interaction, &WalletAction, &mut BackgroundColor), Changed<Interaction>>,
+    mut prompt: ResMut<WalletPrompt>,
+) {
+    for (interaction, action, mut background) in buttons.iter_mut() {
+        match interaction {
+            Interaction::Hovered => *background = BUTTON_HOVER_COLOR.into(),
+            Interaction::None => *background = BUTTON_COLOR.into(),
+            Interaction::Pressed if *action == WalletAction::Offline => commands.insert_resource(WalletChoice::Offline),
+            Interaction::Pressed => prompt.start(*action),
+        }
+    }
+}
+
+// Hands the wallet over once it's unlocked, or shows why it couldn't be
+pub fn finish_wallet_prompt(mut commands: Commands, mut prompt: ResMut<WalletPrompt>) {
+    if !prompt.task.as_ref().is_some_and(|task| task.is_finished()) {
+        return;
+    }
+    let Some(task) = prompt.task.take() else {
+        return;
+    };
+    match task.join() {
+        Ok(Ok(wallet)) => {
+            println!("Unlocked wallet {:?}", wallet.address());
+            commands.insert_resource(WalletChoice::Unlocked(wallet));
+        }
+        Ok(Err(error)) => prompt.status = error,
+        Err(_) => prompt.status = "Unlocking the wallet crashed".to_string(),
+    }
+}
+
+pub fn update_wallet_prompt(
+    prompt: Res<WalletPrompt>,
+    mut password_text: Query<&mut Text, (With<PasswordText>, Without<WalletStatusText>)>,
+    mut status_text: Query<&mut Text, (With<WalletStatusText>, Without<PasswordText>)>,
+) {
+    if !prompt.is_changed() {
+        return;
+    }
+    for mut text in password_text.iter_mut() {
+        text.sections[0].value = format!("Password: {}", "*".repeat(prompt.password.chars().count()));
+    }
+    for mut text in status_text.iter_mut() {
+        text.sections[0].value = prompt.status.clone();
+    }
+}
+
+// Takes the prompt off the screen once the player made a choice
+pub fn close_wallet_prompt(mut commands: Commands, ui: Query<Entity, With<WalletPromptUi>>) {
+    for entity in ui.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<WalletPrompt>();
+}
@@ -0,0 +1,271 @@
+// Wave director. Enemies come in waves read from `assets/waves.json`, each with its own enemy count
+// and composition, spawn interval and speed multiplier, with a breather between waves. Once the
+// listed waves run out the last one repeats, a bit bigger and faster every time.
+
+use bevy::prelude::*;
+use eyre::Result;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::enemies::{random_edge_position, EnemyKind, SpawnEnemy};
+use crate::states::{InGame, RunStats};
+use crate::Enemy;
+
+pub const WAVES_PATH: &str = "assets/waves.json";
+
+#[derive(Clone, Deserialize)]
+pub struct WaveGroup {
+    pub kind: EnemyKind,
+    pub count: u32,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Wave {
+    pub enemies: Vec<WaveGroup>,
+    // Seconds between two spawns
+    pub spawn_interval: f32,
+    #[serde(default = "default_speed_multiplier")]
+    pub speed_multiplier: f32,
+}
+
+fn default_speed_multiplier() -> f32 {
+    1.0
+}
+
+// How the last wave grows each time it repeats
+#[derive(Clone, Deserialize)]
+pub struct Endless {
+    // Extra enemies per group
+    pub count_growth: u32,
+    // Added to the speed multiplier
+    pub speed_growth: f32,
+}
+
+#[derive(Resource, Clone, Deserialize)]
+pub struct WaveTable {
+    pub breather_secs: f32,
+    pub waves: Vec<Wave>,
+    pub endless: Endless,
+}
+
+impl Default for WaveTable {
+    // One enemy every two seconds, the way the game spawned before waves
+    fn default() -> Self {
+        WaveTable {
+            breather_secs: 5.0,
+            waves: vec![Wave {
+                enemies: vec![WaveGroup { kind: EnemyKind::Chaser, count: 10 }],
+                spawn_interval: 2.0,
+                speed_multiplier: 1.0,
+            }],
+            endless: Endless { count_growth: 2, speed_growth: 0.1 },
+        }
+    }
+}
+
+impl WaveTable {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let table: WaveTable = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        if table.waves.is_empty() {
+            eyre::bail!("no waves defined");
+        }
+        Ok(table)
+    }
+
+    // Wave `number`, counting from zero, scaled up past the end of the list
+    fn wave(&self, number: usize) -> Wave {
+        let last = self.waves.len() - 1;
+        let mut wave = self.waves[number.min(last)].clone();
+        let extra = number.saturating_sub(last) as u32;
+        for group in &mut wave.enemies {
+            group.count += self.endless.count_growth * extra;
+        }
+        wave.speed_multiplier += self.endless.speed_growth * extra as f32;
+        wave
+    }
+}
+
+pub fn load_wave_table(mut commands: Commands) {
+    let table = WaveTable::load(WAVES_PATH).unwrap_or_else(|e| {
+        eprintln!("Couldn't load {}, using the default waves: {}", WAVES_PATH, e);
+        WaveTable::default()
+    });
+    commands.insert_resource(table);
+}
+
+enum WavePhase {
+    Breather(Timer),
+    // Spawning `queue` from the back, one every time the timer finishes
+    Spawning { queue: Vec<EnemyKind>, timer: Timer },
+    // Everything spawned, waiting for the player to finish the wave off
+    Clearing,
+}
+
+#[derive(Resource)]
+pub struct WaveDirector {
+    number: usize, // Counting from zero
+    wave: Wave,
+    phase: WavePhase,
+}
+
+#[derive(Component)]
+pub struct WaveText;
+
+pub fn start_waves(mut commands: Commands, table: Res<WaveTable>) {
+    commands.insert_resource(WaveDirector {
+        number: 0,
+        wave: table.wave(0),
+        // A short breather so the player can get their bearings
+        phase: WavePhase::Breather(Timer::from_seconds(table.breather_secs, TimerMode::Once)),
+    });
+    commands.spawn((
+        TextBundle::from_section("", TextStyle { font_size: 28.0, color: Color::WHITE, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                left: Val::Percent(45.0),
+                ..default()
+            }),
+        WaveText,
+        StateScoped(InGame),
+    ));
+}
+
+pub fn end_waves(mut commands: Commands) {
+    commands.remove_resource::<WaveDirector>();
+}
+
+pub fn run_waves(
+    time: Res<Time>,
+    table: Res<WaveTable>,
+    mut director: ResMut<WaveDirector>,
+    mut run_stats: ResMut<RunStats>,
+    mut spawns: EventWriter<SpawnEnemy>,
+    enemies: Query<(), With<Enemy>>,
+) {
+    let director = &mut *director;
+    match &mut director.phase {
+        WavePhase::Breather(timer) => {
+            if timer.tick(time.delta()).finished() {
+                let queue = shuffled_queue(&director.wave);
+                let timer = Timer::from_seconds(director.wave.spawn_interval, TimerMode::Repeating);
+                director.phase = WavePhase::Spawning { queue, timer };
+            }
+        }
+        WavePhase::Spawning { queue, timer } => {
+            if timer.tick(time.delta()).just_finished() {
+                if let Some(kind) = queue.pop() {
+                    spawns.send(SpawnEnemy {
+                        kind,
+                        position: random_edge_position(),
+                        speed_multiplier: director.wave.speed_multiplier,
+                    });
+                }
+            }
+            if queue.is_empty() {
+                director.phase = WavePhase::Clearing;
+            }
+        }
+        WavePhase::Clearing => {
+            // `spawn_enemies` runs later in the frame than this and `enemy_deaths`, so the wave's
+            // last enemy and a splitter's children are already in the query by now
+            if enemies.is_empty() {
+                run_stats.waves_cleared += 1;
+                director.number += 1;
+                director.wave = table.wave(director.number);
+                director.phase = WavePhase::Breather(Timer::from_seconds(table.breather_secs, TimerMode::Once));
+            }
+        }
+    }
+}
+
+pub fn update_wave_text(
+    director: Res<WaveDirector>,
+    enemies: Query<(), With<Enemy>>,
+    mut wave_text: Query<&mut Text, With<WaveText>>,
+) {
+    let text = match &director.phase {
+        WavePhase::Breather(timer) if director.number == 0 => format!("Wave 1 in {}", timer.remaining_secs().ceil()),
+        WavePhase::Breather(timer) => {
+            format!("Wave {} cleared! Next wave in {}", director.number, timer.remaining_secs().ceil())
+        }
+        WavePhase::Spawning { .. } => format!("Wave {}", director.number + 1),
+        WavePhase::Clearing => format!("Wave {}: {} left", director.number + 1, enemies.iter().count()),
+    };
+    for mut wave_text in wave_text.iter_mut() {
+        if wave_text.sections[0].value != text {
+            wave_text.sections[0].value = text.clone();
+        }
+    }
+}
+
+// The wave's enemies in random order, so groups mix
+fn shuffled_queue(wave: &Wave) -> Vec<EnemyKind> {
+    let mut queue: Vec<EnemyKind> = wave.enemies.iter()
+        .flat_map(|group| std::iter::repeat_n(group.kind, group.count as usize))
+        .collect();
+    queue.shuffle(&mut rand::thread_rng());
+    queue
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wave(kind: EnemyKind, count: u32, speed_multiplier: f32) -> Wave {
+        Wave { enemies: vec![WaveGroup { kind, count }], spawn_interval: 1.0, speed_multiplier }
+    }
+
+    fn table() -> WaveTable {
+        WaveTable {
+            breather_secs: 5.0,
+            waves: vec![wave(EnemyKind::Chaser, 3, 1.0), wave(EnemyKind::Shooter, 4, 1.5)],
+            endless: Endless { count_growth: 2, speed_growth: 0.25 },
+        }
+    }
+
+    fn counts(wave: &Wave) -> Vec<(EnemyKind, u32)> {
+        wave.enemies.iter().map(|group| (group.kind, group.count)).collect()
+    }
+
+    #[test]
+    fn scripted_waves_play_as_listed() {
+        let table = table();
+        assert_eq!(counts(&table.wave(0)), vec![(EnemyKind::Chaser, 3)]);
+        assert_eq!(table.wave(0).speed_multiplier, 1.0);
+        // The last listed wave plays once as written before it starts growing
+        assert_eq!(counts(&table.wave(1)), vec![(EnemyKind::Shooter, 4)]);
+        assert_eq!(table.wave(1).speed_multiplier, 1.5);
+    }
+
+    #[test]
+    fn endless_waves_grow_from_the_last_one() {
+        let table = table();
+        assert_eq!(counts(&table.wave(2)), vec![(EnemyKind::Shooter, 6)]);
+        assert_eq!(table.wave(2).speed_multiplier, 1.75);
+        assert_eq!(counts(&table.wave(5)), vec![(EnemyKind::Shooter, 12)]);
+        assert_eq!(table.wave(5).speed_multiplier, 2.5);
+    }
+
+    #[test]
+    fn every_group_of_the_last_wave_grows() {
+        let mut table = table();
+        table.waves[1].enemies.push(WaveGroup { kind: EnemyKind::Charger, count: 1 });
+        assert_eq!(counts(&table.wave(3)), vec![(EnemyKind::Shooter, 8), (EnemyKind::Charger, 5)]);
+    }
+
+    #[test]
+    fn shipped_waves_load() {
+        let table = WaveTable::load(WAVES_PATH).unwrap();
+        assert!(table.wave(table.waves.len() + 10).enemies.iter().all(|group| group.count > 0));
+    }
+
+    #[test]
+    fn empty_wave_list_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("waves.json");
+        std::fs::write(&path, r#"{ "breather_secs": 1, "waves": [], "endless": { "count_growth": 1, "speed_growth": 0.1 } }"#).unwrap();
+        assert!(WaveTable::load(&path).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+// Combat stats of each sword color, read from `assets/weapons.json` so they can be tuned without a
+// rebuild. Colors are looked up by their registry name; colors the file doesn't list use its
+// `default` entry. Special effects stick to the enemies a sword hits.
+
+use bevy::prelude::*;
+use eyre::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::health::Health;
+
+pub const WEAPONS_PATH: &str = "assets/weapons.json";
+
+#[derive(Clone, Deserialize)]
+pub struct WeaponStats {
+    pub damage: f32,
+    // How far in front of the player the swing lands
+    pub reach: f32,
+    // Swing animation frames per second, whatever the swing clips in `assets/animations.json` say
+    pub swing_speed: f32,
+    // Speed an enemy is pushed away with when hit
+    pub knockback: f32,
+    #[serde(default)]
+    pub effect: Option<SpecialEffect>,
+}
+
+impl Default for WeaponStats {
+    // The `default` entry of `assets/weapons.json`, so a missing file plays the same as the shipped one
+    fn default() -> Self {
+        WeaponStats { damage: 1.0, reach: 50.0, swing_speed: 12.0, knockback: 200.0, effect: None }
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum SpecialEffect {
+    Burn { damage_per_sec: f32, secs: f32 },
+    Poison { damage_per_sec: f32, secs: f32 },
+    // Multiplies the enemy's speed by `slow`
+    Freeze { slow: f32, secs: f32 },
+}
+
+#[derive(Resource, Default, Deserialize)]
+pub struct WeaponTable {
+    #[serde(default)]
+    default: WeaponStats,
+    #[serde(default)]
+    colors: HashMap<String, WeaponStats>,
+}
+
+impl WeaponTable {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn stats(&self, color_name: &str) -> &WeaponStats {
+        self.colors.get(color_name).unwrap_or(&self.default)
+    }
+}
+
+pub fn load_weapon_table(mut commands: Commands) {
+    let table = WeaponTable::load(WEAPONS_PATH).unwrap_or_else(|e| {
+        eprintln!("Couldn't load {}, every sword gets the default stats: {}", WEAPONS_PATH, e);
+        WeaponTable::default()
+    });
+    commands.insert_resource(table);
+}
+
+// A special effect running on an enemy. A new hit replaces it.
+#[derive(Component)]
+pub struct StatusEffect {
+    pub effect: SpecialEffect,
+    timer: Timer,
+}
+
+impl StatusEffect {
+    pub fn new(effect: SpecialEffect) -> Self {
+        let secs = match effect {
+            SpecialEffect::Burn { secs, .. } | SpecialEffect::Poison { secs, .. } | SpecialEffect::Freeze { secs, .. } => secs,
+        };
+        StatusEffect { effect, timer: Timer::from_seconds(secs, TimerMode::Once) }
+    }
+
+    // How much the effect multiplies the enemy's speed by
+    pub fn speed_factor(&self) -> f32 {
+        match self.effect {
+            SpecialEffect::Freeze { slow, .. } => slow,
+            _ => 1.0,
+        }
+    }
+
+    fn tint(&self) -> Color {
+        match self.effect {
+            SpecialEffect::Burn { .. } => Color::srgb(1.0, 0.6, 0.3),
+            SpecialEffect::Poison { .. } => Color::srgb(0.5, 1.0, 0.4),
+            SpecialEffect::Freeze { .. } => Color::srgb(0.5, 0.8, 1.0),
+        }
+    }
+}
+
+// Applies damage over time, tints affected enemies and ends effects when they run out
+pub fn tick_status_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut StatusEffect, &mut Health, &mut Sprite)>,
+) {
+    for (entity, mut status, mut health, mut sprite) in query.iter_mut() {
+        if let SpecialEffect::Burn { damage_per_sec, .. } | SpecialEffect::Poison { damage_per_sec, .. } = status.effect {
+            health.damage(damage_per_sec * time.delta_seconds());
+        }
+        if status.timer.tick(time.delta()).finished() {
+            sprite.color = Color::WHITE;
+            commands.entity(entity).remove::<StatusEffect>();
+        } else {
+            sprite.color = status.tint();
+        }
+    }
+}
@@ -10,6 +10,37 @@ const MY_CONTRACT_ABI = [
     "stateMutability": "view",
     "type": "function"
   },
+  {
+    "inputs": [
+      { "internalType": "address", "name": "player", "type": "address" },
+      { "internalType": "uint256", "name": "color", "type": "uint256" }
+    ],
+    "name": "getSwordCountOf",
+    "outputs": [{ "internalType": "uint256", "name": "", "type": "uint256" }],
+    "stateMutability": "view",
+    "type": "function"
+  },
+  {
+    "inputs": [{ "internalType": "address", "name": "player", "type": "address" }],
+    "name": "getSwordCountsOf",
+    "outputs": [
+      { "internalType": "uint256", "name": "", "type": "uint256" },
+      { "internalType": "uint256", "name": "", "type": "uint256" },
+      { "internalType": "uint256", "name": "", "type": "uint256" }
+    ],
+    "stateMutability": "view",
+    "type": "function"
+  },
+  {
+    "inputs": [
+      { "internalType": "bytes32", "name": "role", "type": "bytes32" },
+      { "internalType": "address", "name": "account", "type": "address" }
+    ],
+    "name": "hasRole",
+    "outputs": [{ "internalType": "bool", "name": "", "type": "bool" }],
+    "stateMutability": "view",
+    "type": "function"
+  },
   {
     "inputs": [{ "internalType": "uint256", "name": "color", "type": "uint256" }],
     "name": "incrementSword",
@@ -100,9 +131,13 @@ async function connectWallet() {
 loadDapp()
 
 const onContractInitCallback = async () => {
-  let red = await my_contract.methods.getSwordCount(0).call()
-  let blue = await my_contract.methods.getSwordCount(1).call()
-  let green = await my_contract.methods.getSwordCount(2).call()
+  if (!accounts || accounts.length == 0) {
+    return
+  }
+  let counts = await my_contract.methods.getSwordCountsOf(accounts[0]).call()
+  let red = counts[0]
+  let blue = counts[1]
+  let green = counts[2]
 
   let contract_state = 
     "Red Swords: " + red +
@@ -114,6 +149,17 @@ const onContractInitCallback = async () => {
 
 const onWalletConnectedCallback = async () => {
   console.log(accounts[0])
+  onContractInitCallback() // load the connected wallet's swords
+  showMintControls()
+}
+
+// incrementSword reverts for anyone without MINTER_ROLE, so only minters get the button. Players
+// collect their swords in the game.
+const showMintControls = async () => {
+  const minter_role = web3.utils.keccak256("MINTER_ROLE")
+  const is_minter = await my_contract.methods.hasRole(minter_role, accounts[0]).call()
+  document.getElementById("mint_controls").style.display = is_minter ? "block" : "none"
+  document.getElementById("player_message").style.display = is_minter ? "none" : "block"
 }
 
 const incrementSword = async (color) => {
@@ -126,7 +172,8 @@ const incrementSword = async (color) => {
       document.getElementById("web3_message").textContent="Success.";
       onContractInitCallback() // refresh sword counts
     })
-    .catch((revertReason) => {
-      console.log("ERROR! Transaction reverted: " + revertReason.receipt.transactionHash)
+    .catch((error) => {
+      document.getElementById("web3_message").textContent="Minting failed: " + error.message;
+      console.log("ERROR! Transaction reverted: ", error)
     });
 }